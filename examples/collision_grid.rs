@@ -0,0 +1,79 @@
+use std::time::Instant;
+
+use cgmath::{vec2, Vector2};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use breakout::collision::sweep_circle_collision;
+use breakout::game_level::GameLevel;
+use breakout::simulation::BALL_RADIUS;
+
+// Size of the benchmark level in tiles, 10k bricks in total
+const COLUMNS: usize = 100;
+const ROWS: usize = 100;
+// Ball sweeps timed per run
+const QUERIES: usize = 20_000;
+
+// Times brick collision queries through the level grid against testing every brick,
+// run with `cargo run --release --no-default-features --example collision_grid`
+fn main() {
+    let mut level = GameLevel::new();
+    level.level_width = COLUMNS as u32 * 40;
+    level.level_height = ROWS as u32 * 20;
+    level.set_tiles(vec![vec![2; COLUMNS]; ROWS]);
+
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    let sweeps: Vec<(Vector2<f32>, Vector2<f32>)> = (0..QUERIES)
+        .map(|_| {
+            let center = vec2(
+                rng.gen_range(0.0..level.level_width as f32),
+                rng.gen_range(0.0..level.level_height as f32)
+            );
+            // about as far as the ball gets in a single step
+            let motion = vec2(rng.gen_range(-8.0..8.0), rng.gen_range(-8.0..8.0));
+            (center, motion)
+        })
+        .collect();
+
+    println!("{} bricks, {} ball sweeps", level.bricks.len(), QUERIES);
+    compare(&level, &sweeps);
+
+    // most bricks gone, as near the end of a level
+    let remaining = level.bricks.len() / 10;
+    for i in remaining..level.bricks.len() {
+        let hit_points = level.bricks[i].hit_points;
+        level.damage_brick(i, hit_points);
+    }
+    println!("{} bricks left", remaining);
+    compare(&level, &sweeps);
+}
+
+fn compare(level: &GameLevel, sweeps: &[(Vector2<f32>, Vector2<f32>)]) {
+    let start = Instant::now();
+    let mut every_brick = 0;
+    for (center, motion) in sweeps {
+        for brick in level.bricks.iter() {
+            if !brick.game_object.destroyed && sweep_circle_collision(*center, BALL_RADIUS, *motion, &brick.game_object).is_some() {
+                every_brick += 1;
+            }
+        }
+    }
+    let every_brick_time = start.elapsed();
+
+    let start = Instant::now();
+    let mut grid = 0;
+    for (center, motion) in sweeps {
+        let min = center.zip(center + motion, f32::min) - vec2(BALL_RADIUS, BALL_RADIUS);
+        let max = center.zip(center + motion, f32::max) + vec2(BALL_RADIUS, BALL_RADIUS);
+        for i in level.bricks_in(min, max) {
+            if sweep_circle_collision(*center, BALL_RADIUS, *motion, &level.bricks[i].game_object).is_some() {
+                grid += 1;
+            }
+        }
+    }
+    let grid_time = start.elapsed();
+
+    println!("  every brick: {:>10.3?} ({} hits)", every_brick_time, every_brick);
+    println!("  grid:        {:>10.3?} ({} hits), {:.0}x faster", grid_time, grid,
+             every_brick_time.as_secs_f64() / grid_time.as_secs_f64().max(1e-9));
+}
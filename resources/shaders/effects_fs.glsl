@@ -10,6 +10,7 @@ uniform float     blur_kernel[9];
 uniform bool chaos;
 uniform bool confuse;
 uniform bool shake;
+uniform float brightness;
 
 void main()
 {
@@ -41,4 +42,6 @@ void main()
     {
         color =  texture(scene, TexCoords);
     }
+    // dim the scene (used behind menus and overlays)
+    color.rgb *= brightness;
 }
\ No newline at end of file
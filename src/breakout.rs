@@ -0,0 +1,23 @@
+// Game logic of Breakout: the simulation, levels, power-ups, replays and settings.
+// None of it touches the window, OpenGL or audio, so it builds and tests headless
+// (`cargo test --no-default-features`), the game binary draws and plays it
+
+// `field: field` initialisers, a named binding before returning it and `new` without a
+// `Default` impl are how the rest of the code is written
+#![allow(clippy::redundant_field_names, clippy::let_and_return, clippy::new_without_default)]
+
+pub mod ball;
+pub mod brick;
+pub mod collision;
+pub mod game_level;
+pub mod game_object;
+pub mod level_format;
+pub mod level_generator;
+pub mod level_pack;
+pub mod power_up;
+pub mod progress;
+pub mod replay;
+pub mod score;
+pub mod settings;
+pub mod simulation;
+pub mod timestep;
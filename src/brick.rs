@@ -0,0 +1,52 @@
+use crate::game_object::GameObject;
+use crate::level_format::BrickType;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Brick {
+    pub game_object: GameObject,
+    // brick state
+    pub code: u32,
+    pub points: u32,
+    pub hit_points: u32,
+    pub max_hit_points: u32,
+    pub passthrough_only: bool,
+}
+
+impl Brick {
+    pub fn new(game_object: GameObject, brick_type: &BrickType) -> Self {
+        // a brick always takes at least one hit to destroy
+        let hit_points = brick_type.hit_points.max(1);
+        Brick {
+            game_object: game_object,
+            code: brick_type.code,
+            points: brick_type.points,
+            hit_points: hit_points,
+            max_hit_points: hit_points,
+            passthrough_only: brick_type.passthrough_only,
+        }
+    }
+
+    pub fn is_damaged(&self) -> bool {
+        self.hit_points < self.max_hit_points
+    }
+
+    // part of the hit points left, from 1.0 when untouched down to 0.0
+    pub fn health(&self) -> f32 {
+        self.hit_points as f32 / self.max_hit_points as f32
+    }
+
+    // takes `damage` hit points off the brick (destroying it at zero) and returns the points earned
+    pub fn hit(&mut self, damage: u32) -> u32 {
+        let before = self.hit_points;
+        self.hit_points -= damage.min(self.hit_points);
+        if self.hit_points == 0 {
+            self.game_object.destroyed = true;
+        }
+        // the points are spread over the hits, so all of them add up to the brick's worth
+        self.remaining_points(before) - self.remaining_points(self.hit_points)
+    }
+
+    fn remaining_points(&self, hit_points: u32) -> u32 {
+        self.points * hit_points / self.max_hit_points
+    }
+}
@@ -0,0 +1,120 @@
+use cgmath::{vec2, Vector2, dot};
+use cgmath::prelude::*;
+
+use crate::game_object::GameObject;
+
+// First contact of a moving circle with an obstacle
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Hit {
+    // part of the motion covered before the contact, from 0.0 to 1.0
+    pub time: f32,
+    // unit vector pointing away from the obstacle at the contact
+    pub normal: Vector2<f32>,
+}
+
+// AABB - AABB collision
+pub fn check_square_collision(one: &GameObject, two: &GameObject) -> bool {
+    // collision x-axis?
+    let collision_x = one.position.x + one.size.x >= two.position.x && two.position.x + two.size.x >= one.position.x;
+    // collision y-axis?
+    let collision_y = one.position.y + one.size.y >= two.position.y && two.position.y + two.size.y >= one.position.y;
+    // collision only if on both axes
+    collision_x && collision_y
+}
+
+// swept circle - AABB collision: when a circle at `center` moving by `motion` first touches the box
+pub fn sweep_circle_collision(center: Vector2<f32>, radius: f32, motion: Vector2<f32>, aabb: &GameObject) -> Option<Hit> {
+    let min = aabb.position;
+    let max = aabb.position + aabb.size;
+
+    // already touching: push out along the shortest way, unless moving away anyway
+    let closest = vec2(clamp(center.x, min.x, max.x), clamp(center.y, min.y, max.y));
+    let difference = center - closest;
+    if length(difference) < radius {
+        let normal = if difference.x == 0.0 && difference.y == 0.0 {
+            // the center is inside the box, leave through the nearest side
+            inside_normal(center, min, max)
+        } else {
+            difference.normalize()
+        };
+        return if dot(motion, normal) < 0.0 { Some(Hit { time: 0.0, normal: normal }) } else { None };
+    }
+
+    // otherwise the center travels along a ray against the box grown by the radius:
+    // flat along the sides, rounded around the corners
+    let mut first: Option<Hit> = None;
+    let mut consider = |hit: Hit| {
+        if hit.time >= 0.0 && hit.time <= 1.0 && first.map_or(true, |first| hit.time < first.time) {
+            first = Some(hit);
+        }
+    };
+
+    // sides, only hit from the front and only between their corners
+    if motion.x > 0.0 {
+        let time = (min.x - radius - center.x) / motion.x;
+        let y = center.y + motion.y * time;
+        if y >= min.y && y <= max.y { consider(Hit { time: time, normal: vec2(-1.0, 0.0) }); }
+    } else if motion.x < 0.0 {
+        let time = (max.x + radius - center.x) / motion.x;
+        let y = center.y + motion.y * time;
+        if y >= min.y && y <= max.y { consider(Hit { time: time, normal: vec2(1.0, 0.0) }); }
+    }
+    if motion.y > 0.0 {
+        let time = (min.y - radius - center.y) / motion.y;
+        let x = center.x + motion.x * time;
+        if x >= min.x && x <= max.x { consider(Hit { time: time, normal: vec2(0.0, -1.0) }); }
+    } else if motion.y < 0.0 {
+        let time = (max.y + radius - center.y) / motion.y;
+        let x = center.x + motion.x * time;
+        if x >= min.x && x <= max.x { consider(Hit { time: time, normal: vec2(0.0, 1.0) }); }
+    }
+
+    // corners, the normal points from the corner to the center at the contact
+    for corner in [min, vec2(max.x, min.y), vec2(min.x, max.y), max].iter() {
+        if let Some(time) = ray_circle(center, motion, *corner, radius) {
+            let normal = (center + motion * time - corner).normalize();
+            consider(Hit { time: time, normal: normal });
+        }
+    }
+
+    first
+}
+
+// reflects a velocity off a surface with the given normal
+pub fn reflect(velocity: Vector2<f32>, normal: Vector2<f32>) -> Vector2<f32> {
+    velocity - normal * 2.0 * dot(velocity, normal)
+}
+
+pub fn length(vector: Vector2<f32>) -> f32 {
+    (vector.x.powi(2) + vector.y.powi(2)).sqrt()
+}
+
+pub fn clamp(value: f32, min: f32, max: f32) -> f32 {
+    value.max(min).min(max)
+}
+
+// earliest time a ray enters a circle, if it does so while heading towards its center
+fn ray_circle(origin: Vector2<f32>, direction: Vector2<f32>, center: Vector2<f32>, radius: f32) -> Option<f32> {
+    let offset = origin - center;
+    let a = dot(direction, direction);
+    let b = dot(offset, direction);
+    let c = dot(offset, offset) - radius * radius;
+    let discriminant = b * b - a * c;
+    if a == 0.0 || b >= 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    Some((-b - discriminant.sqrt()) / a)
+}
+
+// normal of the side of a box closest to a point inside it
+fn inside_normal(point: Vector2<f32>, min: Vector2<f32>, max: Vector2<f32>) -> Vector2<f32> {
+    let distances = [
+        (point.x - min.x, vec2(-1.0, 0.0)),
+        (max.x - point.x, vec2(1.0, 0.0)),
+        (point.y - min.y, vec2(0.0, -1.0)),
+        (max.y - point.y, vec2(0.0, 1.0)),
+    ];
+    distances.iter()
+        .fold(distances[0], |nearest, side| if side.0 < nearest.0 { *side } else { nearest })
+        .1
+}
@@ -0,0 +1,86 @@
+use cgmath::{vec2, Vector2};
+
+use crate::level_format::BrickType;
+use crate::level_generator::{GeneratorParams, Pattern};
+
+// Size of a brush in the palette strip at the bottom of the screen
+pub const PALETTE_SLOT_SIZE: Vector2<f32> = Vector2 { x: 48.0, y: 24.0 };
+// Space between (and around) the palette slots
+pub const PALETTE_SLOT_SPACING: f32 = 8.0;
+// Time a status message stays on screen
+const MESSAGE_TIME: f32 = 3.0;
+
+// State of the level editor, the level itself is edited in place
+pub struct Editor {
+    // brick code placed by the left mouse button, 0 erases
+    pub brush: u32,
+    // whether the edited level is being played from the editor
+    pub play_testing: bool,
+    // settings of the last generated layout
+    pub generator: GeneratorParams,
+    // last status message and how much longer it is shown
+    pub message: String,
+    pub message_time: f32,
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Editor {
+            brush: 2,
+            play_testing: false,
+            generator: GeneratorParams::new(0, Pattern::Random),
+            message: String::new(),
+            message_time: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.message_time = (self.message_time - dt).max(0.0);
+    }
+
+    pub fn show_message(&mut self, message: String) {
+        self.message = message;
+        self.message_time = MESSAGE_TIME;
+    }
+
+    // switches the generator to the next pattern family
+    pub fn cycle_pattern(&mut self) {
+        let patterns = Pattern::ALL;
+        let index = patterns.iter().position(|pattern| *pattern == self.generator.pattern).unwrap_or(0);
+        self.generator.pattern = patterns[(index + 1) % patterns.len()];
+    }
+
+    // codes that can be painted with: the eraser followed by the palette in code order
+    pub fn brushes(palette: &[BrickType]) -> Vec<u32> {
+        let mut codes: Vec<u32> = palette.iter().map(|brick_type| brick_type.code).collect();
+        codes.sort_unstable();
+        codes.insert(0, 0);
+        codes
+    }
+
+    // selects the next or previous brush, wrapping around
+    pub fn cycle_brush(&mut self, palette: &[BrickType], forward: bool) {
+        let brushes = Editor::brushes(palette);
+        let count = brushes.len();
+        let index = brushes.iter().position(|code| *code == self.brush).unwrap_or(0);
+        let next = if forward { (index + 1) % count } else { (index + count - 1) % count };
+        self.brush = brushes[next];
+    }
+
+    // top-left corner of the palette slot of the `index`th brush
+    pub fn palette_slot(index: usize, screen_height: u32) -> Vector2<f32> {
+        vec2(
+            PALETTE_SLOT_SPACING + index as f32 * (PALETTE_SLOT_SIZE.x + PALETTE_SLOT_SPACING),
+            screen_height as f32 - PALETTE_SLOT_SIZE.y - PALETTE_SLOT_SPACING
+        )
+    }
+
+    // index of the palette slot under a point, if any
+    pub fn palette_slot_at(position: Vector2<f32>, count: usize, screen_height: u32) -> Option<usize> {
+        (0..count).find(|index| {
+            let slot = Editor::palette_slot(*index, screen_height);
+            position.x >= slot.x && position.x < slot.x + PALETTE_SLOT_SIZE.x &&
+                position.y >= slot.y && position.y < slot.y + PALETTE_SLOT_SIZE.y
+        })
+    }
+}
@@ -1,102 +1,203 @@
 use std::cmp;
+use std::collections::HashSet;
 use std::sync::Mutex;
 use std::ffi::CStr;
+use std::fs;
+use std::path::Path;
 
-use glfw::{Key, Action};
+use glfw::{Key, Action, GamepadButton, MouseButton};
 
-use cgmath::{vec2, Vector2, vec3, Matrix4, ortho, dot};
+use cgmath::{vec2, vec3, Vector2, Vector3, Matrix4, ortho};
 use cgmath::prelude::*;
-use rand::prelude::*;
 use cpal::{Data, Sample, SampleFormat};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 
-use crate::ball::Ball;
+use crate::editor::{Editor, PALETTE_SLOT_SIZE, PALETTE_SLOT_SPACING};
 use crate::game_level::GameLevel;
 use crate::game_object::GameObject;
+use crate::input::{button_name, key_name, Bindings, Gamepad, InputAction, KEY_NAMES};
+use crate::level_format::find_brick_type;
+use crate::level_generator;
+use crate::level_pack::{LevelPack, Unlock};
 use crate::lib::post_processor::PostProcessor;
 use crate::lib::shader::Shader;
 use crate::lib::sprite_renderer::SpriteRenderer;
+use crate::lib::text_renderer::{TextAlign, TextRenderer};
+use crate::lib::texture::Texture2D;
 use crate::particle::ParticleGenerator;
-use crate::power_up::PowerUp;
+use crate::power_up::{PowerUpKind, POWER_UP_KINDS};
+use crate::progress::Progress;
+use crate::replay::{Playback, Replay, MAX_SPEED};
 use crate::resource_manager::ResourceManager;
+use crate::settings::Settings;
+use crate::simulation::{PlayerInput, Simulation};
+use crate::timestep::FixedTimestep;
 
 // Represents the current state of the game
-#[derive(PartialEq)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub enum GameState {
+    GameMenu,
+    GameLevelSelect,
     GameActive,
-    // GameMenu,
-    // GameWin
+    GamePaused,
+    GameLevelComplete,
+    GameWin,
+    GameOver,
+    GameEditor,
+    GameBindings,
 }
 
-// Represents the four possible (collision) directions
-#[derive(PartialEq)]
-enum Direction {
-    Up,
-    Right,
-    Down,
-    Left,
-    None
-}
-
-impl Direction {
-    fn from_i8(value: i8) -> Direction {
-        match value {
-            0 => Direction::Up,
-            1 => Direction::Right,
-            2 => Direction::Down,
-            3 => Direction::Left,
-            _ => Direction::None
-        }
-    }
-}
-
-type Collision = (bool, Direction, Vector2<f32>);
-
 static mut RENDERER: SpriteRenderer = SpriteRenderer {
     shader: Shader { id: 0 },
     quad_vao: 0
 };
 static mut POST_PROCESSOR: PostProcessor = PostProcessor::new_empty();
 static mut PARTICLE_GENERATOR: ParticleGenerator = ParticleGenerator::new_empty();
+static mut TEXT: TextRenderer = TextRenderer::new_empty();
+
+// Time the level complete screen is shown before the next level starts
+const LEVEL_TRANSITION_TIME: f32 = 2.5;
+// Where campaign progress is kept between runs
+const PROGRESS_PATH: &str = "saves/progress.cfg";
+// Keys bound to the player's actions by default, and as changed on the rebinding screen
+const DEFAULT_BINDINGS_PATH: &str = "resources/bindings.cfg";
+const BINDINGS_PATH: &str = "saves/bindings.cfg";
+// Directory the levels of the campaign are found in
+const LEVEL_DIRECTORY: &str = "resources/levels";
+// Where the last game played is saved as a replay
+const REPLAY_PATH: &str = "saves/last.replay";
+// Seconds a replay skips back or ahead when scrubbing
+const SCRUB_TIME: f32 = 5.0;
+// Gamepad buttons that open the screens of the main menu, like their keys they can't be rebound
+const LEVEL_SELECT_BUTTON: GamepadButton = GamepadButton::ButtonY;
+const EDITOR_BUTTON: GamepadButton = GamepadButton::ButtonLeftBumper;
+const REPLAY_BUTTON: GamepadButton = GamepadButton::ButtonX;
+const BINDINGS_BUTTON: GamepadButton = GamepadButton::ButtonRightBumper;
+// Time a gamepad being connected or disconnected is shown
+const GAMEPAD_MESSAGE_TIME: f32 = 2.5;
+// Size of a power-up icon in the active effects list of the HUD
+const EFFECT_ICON_SIZE: Vector2<f32> = Vector2 { x: 48.0, y: 12.0 };
+// Seconds before running out at which an effect starts flashing
+const EFFECT_WARNING_TIME: f32 = 3.0;
+// Brightness a brick's color fades to as it runs out of hit points
+const DAMAGE_TINT: f32 = 0.4;
 
 lazy_static! {
-    static ref RESOURCES: Mutex<ResourceManager<'static>> = Mutex::new(ResourceManager::new());
+    static ref RESOURCES: Mutex<ResourceManager> = Mutex::new(ResourceManager::new());
     // static ref PLAYER: Mutex<GameObject> = Mutex::new(GameObject::new_empty());
 }
 
-// Initial size of the player paddle
-const PLAYER_SIZE: Vector2<f32> = vec2(100.0, 20.0);
-// Initial velocity of the player paddle
-const PLAYER_VELOCITY: f32 = 500.0;
-// Initial velocity of the Ball
-const INITIAL_BALL_VELOCITY: Vector2<f32> = vec2(100.0, -350.0);
-// Radius of the ball object
-const BALL_RADIUS: f32 = 12.5;
+// Positions of the moving objects before the last simulation step, by object id so that
+// objects removed during the step don't shift the others, rendering interpolates from these
+// towards the current ones
+struct PreviousPositions {
+    player: Vector2<f32>,
+    balls: Vec<(u32, Vector2<f32>)>,
+    lasers: Vec<(u32, Vector2<f32>)>,
+    power_ups: Vec<(u32, Vector2<f32>)>,
+}
+
+impl PreviousPositions {
+    fn capture(sim: &Simulation) -> Self {
+        PreviousPositions {
+            player: sim.player.position,
+            balls: sim.balls.iter().map(|ball| (ball.game_object.id, ball.game_object.position)).collect(),
+            lasers: sim.lasers.iter().map(|laser| (laser.id, laser.position)).collect(),
+            power_ups: sim.power_ups.iter().map(|pu| (pu.game_object.id, pu.game_object.position)).collect(),
+        }
+    }
+}
+
+// moves an object to where it is drawn between its previous and current position,
+// objects that came into play during the last step have no previous position yet
+fn interpolate(object: &mut GameObject, previous: &[(u32, Vector2<f32>)], alpha: f32) {
+    if let Some((_, position)) = previous.iter().find(|(id, _)| *id == object.id) {
+        object.position = position.lerp(object.position, alpha);
+    }
+}
+
+// A power-up effect as shown on the HUD, with all its layers taken together
+#[derive(Copy, Clone, Debug)]
+pub struct EffectStatus {
+    pub kind: &'static PowerUpKind,
+    // seconds until its last layer runs out
+    pub duration: f32,
+    pub layers: u32,
+}
 
 pub struct Game {
     pub state: GameState,
     pub width: u32,
     pub height: u32,
-    pub player: GameObject,
-    pub ball: Ball,
-    levels: Vec<GameLevel>,
-    actual_level: usize,
-    power_ups: Vec<PowerUp>,
-    shake_time: f32,
+    // game logic, drawn by the game but otherwise independent of it
+    pub sim: Simulation,
+    // fixed rate the simulation is stepped at
+    pub timestep: FixedTimestep,
+    // paddle controls sampled by the last process_input
+    input: PlayerInput,
+    previous: PreviousPositions,
+    // keys whose press has already been handled, until they are released
+    keys_processed: HashSet<Key>,
+    pub bindings: Bindings,
+    // action picked on the rebinding screen, and whether the next key pressed is bound to it
+    binding_selection: usize,
+    capturing_key: bool,
+    pub gamepad: Gamepad,
+    // last gamepad connection change and how much longer it is shown
+    gamepad_message: String,
+    gamepad_message_time: f32,
+    // campaign progress, saved whenever a new level is reached
+    pub progress: Progress,
+    // manifest of the campaign, with an entry for each of the loaded levels
+    pub pack: LevelPack,
+    pub editor: Editor,
+    // tile of the edited level under the mouse cursor
+    editor_hover: Option<(usize, usize)>,
+    // time left on the level complete screen
+    transition_time: f32,
+    settings: Settings,
+    // game being recorded, and the replay of the last one
+    recording: Option<Replay>,
+    pub last_replay: Option<Replay>,
+    // replay being played back instead of the keyboard
+    pub playback: Option<Playback>,
+    // level to go back to once the replay is stopped
+    level_before_replay: usize,
 }
 
 impl Game {
-    pub fn new(width: u32, height: u32) -> Self {
+    pub fn new(width: u32, height: u32, settings: &Settings) -> Self {
+        let mut sim = Simulation::new(width, height);
+        configure_simulation(&mut sim, settings);
+        sim.seed = settings.seed.unwrap_or_else(rand::random);
+        sim.new_game();
+        let previous = PreviousPositions::capture(&sim);
+
         Game {
-            state: GameState::GameActive,
+            state: GameState::GameMenu,
             width: width,
             height: height,
-            player: GameObject::new_empty(),
-            ball: Ball::new_empty(),
-            levels: Vec::new(),
-            actual_level: 0,
-            power_ups: Vec::new(),
-            shake_time: 0.0
+            timestep: FixedTimestep::new(settings.update_rate, settings.max_catch_up_steps),
+            sim: sim,
+            input: PlayerInput::default(),
+            previous: previous,
+            keys_processed: HashSet::new(),
+            bindings: Bindings::load(DEFAULT_BINDINGS_PATH, BINDINGS_PATH),
+            binding_selection: 0,
+            capturing_key: false,
+            gamepad: Gamepad::new(),
+            gamepad_message: String::new(),
+            gamepad_message_time: 0.0,
+            progress: Progress::load(PROGRESS_PATH),
+            pack: LevelPack::default(),
+            editor: Editor::new(),
+            editor_hover: None,
+            transition_time: 0.0,
+            settings: settings.clone(),
+            recording: None,
+            last_replay: None,
+            playback: None,
+            level_before_replay: 0,
         }
     }
 
@@ -117,6 +218,11 @@ impl Game {
             "resources/shaders/effects_fs.glsl",
             "effects"
         );
+        let text_shader = RESOURCES.lock().unwrap().load_shader(
+            "resources/shaders/text_vs.glsl",
+            "resources/shaders/text_fs.glsl",
+            "text"
+        );
 
         // configure shaders
         let projection: Matrix4<f32> = ortho(0.0, self.width as f32, self.height as f32, 0.0, -1.0, 1.0);
@@ -127,53 +233,51 @@ impl Game {
         sprite_shader.set_mat4(text, &projection);
         particle_shader.use_program();
         particle_shader.set_mat4(text, &projection);
+        text_shader.use_program();
+        text_shader.set_mat4(text, &projection);
 
         // load textures
         RESOURCES.lock().unwrap().load_texture("resources/textures/background.jpg", false, "background");
-        let ball_texture = RESOURCES.lock().unwrap().load_texture("resources/textures/awesomeface.png", true, "face");
+        RESOURCES.lock().unwrap().load_texture("resources/textures/awesomeface.png", true, "face");
         RESOURCES.lock().unwrap().load_texture("resources/textures/block.png", false, "block");
         RESOURCES.lock().unwrap().load_texture("resources/textures/block_solid.png", false, "block_solid");
-        let player_texture = RESOURCES.lock().unwrap().load_texture("resources/textures/paddle.png", true, "paddle");
+        RESOURCES.lock().unwrap().load_texture("resources/textures/paddle.png", true, "paddle");
+        RESOURCES.lock().unwrap().load_texture("resources/textures/white.png", true, "white");
         let particle_texture = RESOURCES.lock().unwrap().load_texture("resources/textures/particle.png", true, "particle");
-        RESOURCES.lock().unwrap().load_texture("resources/textures/powerup_sticky.png", true, "powerup_sticky");
-        RESOURCES.lock().unwrap().load_texture("resources/textures/powerup_speed.png", true, "powerup_speed");
-        RESOURCES.lock().unwrap().load_texture("resources/textures/powerup_passthrough.png", true, "powerup_passthrough");
-        RESOURCES.lock().unwrap().load_texture("resources/textures/powerup_increase.png", true, "powerup_increase");
-        RESOURCES.lock().unwrap().load_texture("resources/textures/powerup_confuse.png", true, "powerup_confuse");
-        RESOURCES.lock().unwrap().load_texture("resources/textures/powerup_chaos.png", true, "powerup_chaos");
-
-        // load levels
-        let mut one = GameLevel::new();
-        let mut two = GameLevel::new();
-        let mut three = GameLevel::new();
-        let mut four = GameLevel::new();
-        one.load(RESOURCES.lock().unwrap(), "resources/levels/one.lvl", self.width, self.height / 2 );
-        two.load(RESOURCES.lock().unwrap(), "resources/levels/two.lvl", self.width, self.height / 2 );
-        three.load(RESOURCES.lock().unwrap(), "resources/levels/three.lvl", self.width, self.height / 2 );
-        four.load(RESOURCES.lock().unwrap(), "resources/levels/four.lvl", self.width, self.height / 2 );
-        self.levels.push(one);
-        self.levels.push(two);
-        self.levels.push(three);
-        self.levels.push(four);
+        for kind in POWER_UP_KINDS.iter() {
+            RESOURCES.lock().unwrap().load_texture(kind.texture, true, &kind.texture_name());
+        }
+
+        // load levels along with the textures they name, a broken level (or one naming a
+        // texture that can't be loaded) is reported and left out of the campaign
+        let pack = LevelPack::discover(LEVEL_DIRECTORY);
+        self.pack.name = pack.name;
+        for entry in pack.levels {
+            let path = format!("{}/{}", LEVEL_DIRECTORY, entry.file);
+            let mut level = GameLevel::new();
+            let loaded = level.load(&path, self.width, self.height / 2)
+                .map_err(|error| error.to_string())
+                .and_then(|()| load_level_textures(&level));
+            match loaded {
+                Ok(()) => {
+                    self.sim.levels.push(level);
+                    self.pack.levels.push(entry);
+                },
+                Err(error) => println!("ERROR::LEVEL: Failed to load {}: {}", path, error),
+            }
+        }
+        if self.sim.levels.is_empty() {
+            panic!("No playable levels in {}", LEVEL_DIRECTORY);
+        }
+        // continue the campaign where it was left
+        self.sim.actual_level = cmp::min(self.progress.highest_level, self.sim.levels.len() - 1);
 
         // set render-specific controls
         RENDERER = SpriteRenderer::new(sprite_shader);
-        PARTICLE_GENERATOR = ParticleGenerator::new(particle_shader, particle_texture, 500);
+        PARTICLE_GENERATOR = ParticleGenerator::new(particle_shader, particle_texture, 1500);
         POST_PROCESSOR = PostProcessor::new(effects_shader, self.width as i32, self.height as i32);
-
-        // Player initialization
-        let player_pos = vec2(
-            self.width as f32 / 2.0 - PLAYER_SIZE.x / 2.0,
-            self.height as f32 - PLAYER_SIZE.y
-        );
-        self.player = GameObject::new(player_pos, PLAYER_SIZE, vec2(0.0, 0.0), vec3(1.0, 1.0 ,1.0), player_texture);
-
-        // Ball initialization
-        let ball_pos = player_pos + vec2(
-            PLAYER_SIZE.x / 2.0 - BALL_RADIUS,
-            -BALL_RADIUS * 2.0
-        );
-        self.ball = Ball::new(ball_pos, BALL_RADIUS, INITIAL_BALL_VELOCITY, ball_texture);
+        TEXT = TextRenderer::new(text_shader);
+        TEXT.load("resources/fonts/DejaVuSansMono.ttf", 24);
 
         // Sound initialization
         let host = cpal::default_host();
@@ -199,428 +303,1030 @@ impl Game {
         stream.play().unwrap();
     }
 
-    pub fn update(&mut self, dt: f32) {
-        // update objects
-        self.ball.move_ball(dt, self.width);
-        // check for collisions
-        self.do_collisions();
-        // update particles
-        unsafe {
-            PARTICLE_GENERATOR.update(
-                dt,
-                &self.ball.game_object, 
-                2,
-                vec2(self.ball.radius / 2.0, self.ball.radius / 2.0)
-            );
-        }
-        // update PowerUps
-        self.update_power_ups(dt);
-        // update effects
-        if self.shake_time > 0.0 {
-            self.shake_time -= dt;
-            if self.shake_time <= 0.0 {
-                unsafe {
-                    POST_PROCESSOR.shake = false;
-                }
+    pub fn update(&mut self, frame_time: f32) {
+        self.gamepad_message_time = (self.gamepad_message_time - frame_time).max(0.0);
+        if self.state == GameState::GameLevelComplete {
+            // move on to the next level once the transition is over
+            self.transition_time -= frame_time;
+            if self.transition_time <= 0.0 {
+                self.set_state(GameState::GameActive);
             }
         }
-        // check loss condition
-        if self.ball.game_object.position.y >= self.height as f32 {
-            self.reset_level();
-            self.reset_player();
+        if self.state == GameState::GameEditor {
+            self.editor.update(frame_time);
+        }
+
+        // the simulation is frozen outside of actual play
+        if self.state != GameState::GameActive {
+            return;
+        }
+
+        // replays can be played faster or slower
+        let speed = self.playback.as_ref().map_or(1.0, |playback| playback.speed);
+        let steps = self.timestep.advance(frame_time * speed);
+        for _i in 0..steps {
+            self.step(self.timestep.step);
+            // stop stepping once the level has been won
+            if self.state != GameState::GameActive {
+                break;
+            }
         }
     }
 
-    pub unsafe fn render(&self, time: f32) {
-        if self.state == GameState::GameActive {
-            // begin rendering to postprocessing framebuffer
-            POST_PROCESSOR.begin_render();
-
-            // Draw background
-            let background_tex = RESOURCES.lock().unwrap().get_texture("background");
-            RENDERER.draw_sprite(&background_tex, vec2(0.0, 0.0), vec2(self.width as f32, self.height as f32), 0.0, vec3(1.0, 1.0, 1.0));
-            // Draw level
-            self.levels[self.actual_level].draw(&RENDERER);
-            // draw player
-            self.player.draw(&RENDERER);
-            // draw powerups
-            for power_up in &self.power_ups {
-                if !power_up.game_object.destroyed {
-                    power_up.game_object.draw(&RENDERER);
-                }
+    // advances the game by exactly one fixed step
+    fn step(&mut self, dt: f32) {
+        // a replay provides the controls of every step, until it runs out
+        if let Some(playback) = &mut self.playback {
+            match playback.next_input(&self.sim) {
+                Some(input) => self.input = input,
+                None => return,
             }
-            // draw particles	
-            PARTICLE_GENERATOR.draw();
-            // draw ball
-            self.ball.draw(&RENDERER);
-
-            // end rendering to postprocessing framebuffer
-            POST_PROCESSOR.end_render();
-            // render postprocessing quad
-            POST_PROCESSOR.render(time);
-        }
-    }
-
-    pub fn process_input(&mut self, window: &glfw::Window, dt: f32) {
-        if self.state == GameState::GameActive {
-            let velocity = PLAYER_VELOCITY * dt;
-            // move paddle
-            if window.get_key(Key::A) == Action::Press {
-                if self.player.position.x >= 0.0 {
-                    self.player.position.x -= velocity;
-                    if self.ball.stuck {
-                        self.ball.game_object.position.x -= velocity;
-                    }
-                }
+        }
+        if let Some(recording) = &mut self.recording {
+            recording.inputs.push(self.input);
+        }
+        self.previous = PreviousPositions::capture(&self.sim);
+        // advance game logic
+        self.sim.process_input(&self.input, dt);
+        self.sim.update(dt);
+        // update particles, every ball leaves its own trail
+        unsafe {
+            for ball in self.sim.balls.iter() {
+                PARTICLE_GENERATOR.spawn(&ball.game_object, 2, vec2(ball.radius / 2.0, ball.radius / 2.0), &mut self.sim.particle_rng);
             }
-            if window.get_key(Key::D) == Action::Press {
-                if self.player.position.x <= self.width as f32 - self.player.size.x {
-                    self.player.position.x += velocity;
-                    if self.ball.stuck {
-                        self.ball.game_object.position.x += velocity;
-                    }
-                }
+            PARTICLE_GENERATOR.update(dt);
+        }
+        // check loss and win conditions
+        if self.editor.play_testing && (self.sim.is_game_over() || self.sim.is_level_completed()) {
+            // a play-test ends back in the editor, without touching the campaign
+            self.set_state(GameState::GameEditor);
+        } else if self.sim.is_game_over() {
+            self.set_state(GameState::GameOver);
+        } else if self.sim.is_level_completed() {
+            if self.sim.is_last_level() {
+                self.set_state(GameState::GameWin);
+            } else {
+                self.set_state(GameState::GameLevelComplete);
             }
-            if window.get_key(Key::Space) == Action::Press {
-                self.ball.stuck = false;
+        }
+    }
+
+    // points scored in the current game
+    pub fn score(&self) -> u32 {
+        self.sim.score.points
+    }
+
+    // timed power-up effects currently active, in the order they were picked up
+    pub fn active_effects(&self) -> Vec<EffectStatus> {
+        let mut effects: Vec<EffectStatus> = Vec::new();
+        for effect in self.sim.active_effects.iter() {
+            match effects.iter_mut().find(|status| status.kind.name == effect.kind.name) {
+                Some(status) => {
+                    status.duration = status.duration.max(effect.duration);
+                    status.layers += 1;
+                },
+                None => effects.push(EffectStatus { kind: effect.kind, duration: effect.duration, layers: 1 }),
             }
         }
+        effects
     }
 
-    pub fn reset_level(&mut self) {
-        match self.actual_level {
-            0 => {
-                let mut one = GameLevel::new();
-                one.load(RESOURCES.lock().unwrap(), "resources/levels/one.lvl", self.width, self.height / 2 );
-                self.levels[0] = one;
+    // switches to another state, running the exit hook of the old state and the entry hook of the new one
+    pub fn set_state(&mut self, state: GameState) {
+        let previous = self.state;
+        self.exit_state(previous, state);
+        self.state = state;
+        self.enter_state(state, previous);
+    }
+
+    fn exit_state(&mut self, state: GameState, next: GameState) {
+        match state {
+            GameState::GameActive => {
+                // drop any held controls so they don't leak into the next round
+                self.input = PlayerInput::default();
+                if next != GameState::GamePaused {
+                    // also disables all active powerups and post-processing effects
+                    self.sim.reset_player();
+                }
             },
-            1 => {
-                let mut two = GameLevel::new();
-                two.load(RESOURCES.lock().unwrap(), "resources/levels/two.lvl", self.width, self.height / 2 );
-                self.levels[1] = two;
+            GameState::GameLevelComplete => {
+                // the next level is started by the entry of the active state
+                self.sim.actual_level += 1;
             },
-            2 => {
-                let mut three = GameLevel::new();
-                three.load(RESOURCES.lock().unwrap(), "resources/levels/three.lvl", self.width, self.height / 2 );
-                self.levels[2] = three;
+            GameState::GameWin => {
+                // a new campaign starts from the first level again
+                self.sim.set_level(0);
             },
-            3 => {
-                let mut four = GameLevel::new();
-                four.load(RESOURCES.lock().unwrap(), "resources/levels/four.lvl", self.width, self.height / 2 );
-                self.levels[3] = four;
+            // leaving the editor (other than to play-test) drops unsaved changes
+            GameState::GameEditor if next != GameState::GameActive => {
+                self.reload_level();
+            },
+            GameState::GameBindings => {
+                self.capturing_key = false;
+                self.bindings.save();
             },
             _ => ()
         }
     }
 
-    pub fn reset_player(&mut self) {
-        // reset player/ball stats
-        let player_pos = vec2(
-            self.width as f32 / 2.0 - PLAYER_SIZE.x / 2.0,
-            self.height as f32 - PLAYER_SIZE.y
-        );
-        let ball_pos = player_pos + vec2(
-            PLAYER_SIZE.x / 2.0 - BALL_RADIUS,
-            -BALL_RADIUS * 2.0
-        );
-        
-        self.player.size = PLAYER_SIZE;
-        self.player.position = player_pos;
-
-        self.ball.reset(ball_pos, INITIAL_BALL_VELOCITY);
-
-        // also disable all active powerups
-        self.player.color = vec3(1.0, 1.0, 1.0);
-        unsafe {
-            POST_PROCESSOR.confuse = false;
-            POST_PROCESSOR.chaos = false;
-        }
-
-        self.power_ups.clear();
-    }
-
-    pub fn do_collisions(&mut self) {
-        for brick in &mut self.levels[self.actual_level].bricks {
-            if !brick.destroyed {
-                let collision: Collision = check_circle_collision(&self.ball, &brick);
-
-                if collision.0 { // if collision is true
-                    // destroy block if not solid
-                    if !brick.is_solid {
-                        brick.destroyed = true;
-                        if let Some(power_up) = spawn_power_ups(brick.position) {
-                            self.power_ups.push(power_up);
-                        }
-                    } else { // if block is solid, enable shake effect
-                        self.shake_time = 0.05;
-                        unsafe {
-                            POST_PROCESSOR.shake = true;
-                        }
+    fn enter_state(&mut self, state: GameState, previous: GameState) {
+        match state {
+            GameState::GameActive => {
+                if previous != GameState::GamePaused {
+                    // lives carry over from one level to the next, anything else is a new game
+                    let new_game = previous != GameState::GameLevelComplete;
+                    match &self.playback {
+                        // a replay brings its own seed and level, whatever the screen before left behind
+                        Some(playback) if new_game => playback.replay.start(&mut self.sim),
+                        _ => {
+                            if new_game {
+                                self.sim.seed = self.settings.seed.unwrap_or_else(rand::random);
+                                self.sim.new_game();
+                            }
+                            // start the selected level from scratch
+                            self.sim.set_level(self.sim.actual_level);
+                        },
                     }
-                    // collision resolution
-                    let dir = collision.1;
-                    let diff_vector = collision.2;
-                    let mut penetration = 0.0;
-
-                    if !(self.ball.passthrough && !brick.is_solid) {
-                        match dir {
-                            Direction::Left | Direction::Right => {
-                                self.ball.game_object.velocity.x = -self.ball.game_object.velocity.x; // reverse horizontal velocity
-                                // relocate
-                                penetration = self.ball.radius - diff_vector.x.abs();
-                            },
-                            Direction::Up | Direction::Down => {
-                                self.ball.game_object.velocity.y = -self.ball.game_object.velocity.y; // reverse vertical velocity
-                                // relocate
-                                penetration = self.ball.radius - diff_vector.y.abs();
-                            },
-                            _ => ()
-                        }
-    
-                        match dir {
-                            Direction::Left => self.ball.game_object.position.x += penetration, // move ball to right
-                            Direction::Right => self.ball.game_object.position.x -= penetration, // move ball to left
-                            Direction::Up => self.ball.game_object.position.y += penetration, // move ball back up
-                            Direction::Down => self.ball.game_object.position.y -= penetration, // move ball back down
-                            _ => ()
-                        }
+                    // record every game played for real
+                    if new_game && self.playback.is_none() && previous != GameState::GameEditor {
+                        self.recording = Some(Replay::record(&self.sim, self.settings.update_rate));
                     }
                 }
+                if previous == GameState::GameEditor {
+                    self.editor.play_testing = true;
+                }
+                // don't interpolate from wherever objects were before this state
+                self.previous = PreviousPositions::capture(&self.sim);
+            },
+            GameState::GameMenu | GameState::GameLevelSelect | GameState::GameEditor => {
+                self.finish_recording();
+                // a play-test left for the menu drops the unsaved changes as well
+                if self.editor.play_testing && state != GameState::GameEditor {
+                    self.reload_level();
+                }
+                self.editor.play_testing = false;
+                // show the level as it was laid out
+                self.sim.reset_level();
+                self.sim.reset_player();
+                self.previous = PreviousPositions::capture(&self.sim);
+            },
+            GameState::GameLevelComplete => {
+                self.transition_time = LEVEL_TRANSITION_TIME;
+                // watching a replay doesn't unlock anything
+                if self.playback.is_none() {
+                    self.progress.reach_level(self.sim.actual_level + 1);
+                }
+            },
+            GameState::GameWin => {
+                self.finish_recording();
+                if self.playback.is_none() {
+                    self.progress.complete_campaign();
+                }
+            },
+            GameState::GameOver => {
+                self.finish_recording();
+            },
+            _ => ()
+        }
+    }
+
+    // ends the game being recorded, keeping it as the last replay
+    fn finish_recording(&mut self) {
+        if let Some(mut replay) = self.recording.take() {
+            replay.score = self.sim.score.points;
+            replay.save(REPLAY_PATH);
+            self.last_replay = Some(replay);
+        }
+    }
+
+    // plays a replay back from its start, if it was recorded on the levels loaded now
+    fn start_replay(&mut self, replay: Replay) {
+        let levels: Vec<String> = self.sim.levels.iter().map(|level| level.file_path.clone()).collect();
+        if replay.levels != levels || replay.width != self.width || replay.height != self.height {
+            println!("ERROR::REPLAY: The replay was recorded with other levels or another screen size");
+            return;
+        }
+        // the replay is started by the entry of the active state, after the hooks of this one
+        self.level_before_replay = if self.state == GameState::GameWin { 0 } else { self.sim.actual_level };
+        // step at the recorded rate, with room to catch up at the highest speed
+        self.timestep = FixedTimestep::new(replay.update_rate, self.settings.max_catch_up_steps * MAX_SPEED as u32);
+        self.playback = Some(Playback::new(replay));
+        self.set_state(GameState::GameActive);
+    }
+
+    fn stop_replay(&mut self) {
+        self.playback = None;
+        configure_simulation(&mut self.sim, &self.settings);
+        self.timestep = FixedTimestep::new(self.settings.update_rate, self.settings.max_catch_up_steps);
+        self.set_state(GameState::GameMenu);
+        // back to the level shown before the replay, not wherever it was stopped
+        self.sim.set_level(self.level_before_replay);
+        self.previous = PreviousPositions::capture(&self.sim);
+    }
+
+    // jumps the replay being played back to another time
+    fn scrub_replay(&mut self, seconds: f32) {
+        if let Some(playback) = &mut self.playback {
+            let ticks = (seconds.abs() * playback.replay.update_rate) as usize;
+            let tick = if seconds < 0.0 { playback.tick.saturating_sub(ticks) } else { playback.tick + ticks };
+            playback.seek(&mut self.sim, tick);
+            self.resume_playback();
+        }
+    }
+
+    // carries on playing a replay whose simulation was moved to another time, from whichever
+    // screen it was on; the state hooks are skipped as they would reset the simulation
+    fn resume_playback(&mut self) {
+        self.recording = None;
+        self.transition_time = 0.0;
+        self.input = PlayerInput::default();
+        self.state = GameState::GameActive;
+        self.previous = PreviousPositions::capture(&self.sim);
+    }
+
+    pub unsafe fn render(&self, time: f32) {
+        match self.state {
+            GameState::GameMenu => self.render_menu(time),
+            GameState::GameLevelSelect => self.render_level_select(time),
+            GameState::GameActive => self.render_active(time),
+            GameState::GamePaused => self.render_paused(time),
+            GameState::GameLevelComplete => self.render_level_complete(time),
+            GameState::GameWin => self.render_win(time),
+            GameState::GameOver => self.render_game_over(time),
+            GameState::GameEditor => self.render_editor(time),
+            GameState::GameBindings => self.render_bindings(time),
+        }
+        if self.gamepad_message_time > 0.0 {
+            TEXT.render_text(&self.gamepad_message, vec2(self.width as f32 / 2.0, 40.0), 0.75, vec3(1.0, 0.8, 0.2), TextAlign::Center);
+        }
+        if let Some(playback) = &self.playback {
+            let status = if playback.is_finished() { "END OF REPLAY" } else { "REPLAY" };
+            let replay_time = format!("{} x{} {} / {}", status, playback.speed, clock(playback.time()), clock(playback.replay.duration()));
+            TEXT.render_text(&replay_time, vec2(self.width as f32 / 2.0, self.height as f32 - 60.0), 0.75, vec3(1.0, 0.8, 0.2), TextAlign::Center);
+            let controls = format!(
+                "{}/{} speed, {}/{} to scrub, {} to stop",
+                self.menu_hint(Key::W, GamepadButton::ButtonDpadUp), self.menu_hint(Key::S, GamepadButton::ButtonDpadDown),
+                self.menu_hint(Key::Left, GamepadButton::ButtonDpadLeft), self.menu_hint(Key::Right, GamepadButton::ButtonDpadRight),
+                self.describe(InputAction::Menu)
+            );
+            TEXT.render_text(&controls, vec2(self.width as f32 / 2.0, self.height as f32 - 40.0), 0.5, vec3(0.8, 0.8, 0.8), TextAlign::Center);
+        }
+    }
+
+    unsafe fn render_menu(&self, time: f32) {
+        self.render_scene(time, 0.5);
+        let center = self.width as f32 / 2.0;
+        let middle = self.height as f32 / 2.0;
+        TEXT.render_text("BREAKOUT", vec2(center, middle - 80.0), 2.0, vec3(1.0, 1.0, 1.0), TextAlign::Center);
+        TEXT.render_text(&format!("Press {} to start", self.menu_hint(Key::Enter, GamepadButton::ButtonA)), vec2(center, middle), 1.0, vec3(1.0, 1.0, 1.0), TextAlign::Center);
+        let options = [
+            (Key::L, LEVEL_SELECT_BUTTON, "to select a level"),
+            (Key::E, EDITOR_BUTTON, "to edit the level"),
+            (Key::R, REPLAY_BUTTON, "to watch the last replay"),
+            (Key::B, BINDINGS_BUTTON, "to change the controls"),
+        ];
+        for (i, (key, button, text)) in options.iter().enumerate() {
+            let option = format!("Press {} {}", self.menu_hint(*key, *button), text);
+            TEXT.render_text(&option, vec2(center, middle + 30.0 + 25.0 * i as f32), 0.75, vec3(0.8, 0.8, 0.8), TextAlign::Center);
+        }
+    }
+
+    unsafe fn render_level_select(&self, time: f32) {
+        // pulse the previewed level to show it can be changed
+        self.render_scene(time, 0.75 + 0.25 * (time * 4.0).sin());
+        let center = self.width as f32 / 2.0;
+        let middle = self.height as f32 / 2.0;
+        if let Some(pack_name) = &self.pack.name {
+            TEXT.render_text(pack_name, vec2(center, middle - 80.0), 1.0, vec3(1.0, 1.0, 1.0), TextAlign::Center);
+        }
+        TEXT.render_text(&format!("< {} >", self.level_name()), vec2(center, middle), 1.5, vec3(1.0, 1.0, 1.0), TextAlign::Center);
+        if let Some(author) = &self.sim.levels[self.sim.actual_level].info.author {
+            TEXT.render_text(&format!("by {}", author), vec2(center, middle - 30.0), 0.75, vec3(0.8, 0.8, 0.8), TextAlign::Center);
+        }
+        let controls = format!(
+            "{}/{} to choose, {} to play, {} to go back",
+            self.describe(InputAction::MoveLeft), self.describe(InputAction::MoveRight),
+            self.menu_hint(Key::Enter, GamepadButton::ButtonA), self.describe(InputAction::Menu)
+        );
+        TEXT.render_text(&controls, vec2(center, middle + 50.0), 0.75, vec3(0.8, 0.8, 0.8), TextAlign::Center);
+    }
+
+    unsafe fn render_active(&self, time: f32) {
+        self.render_scene(time, 1.0);
+        self.render_hud();
+        if self.editor.play_testing {
+            TEXT.render_text("PLAY-TEST - T to return to the editor", vec2(self.width as f32 / 2.0, self.height as f32 - 60.0), 0.75, vec3(1.0, 0.8, 0.2), TextAlign::Center);
+        }
+    }
+
+    unsafe fn render_paused(&self, time: f32) {
+        self.render_scene(time, 0.4);
+        self.render_hud();
+        let center = self.width as f32 / 2.0;
+        let middle = self.height as f32 / 2.0;
+        TEXT.render_text("PAUSED", vec2(center, middle - 40.0), 2.0, vec3(1.0, 1.0, 1.0), TextAlign::Center);
+        let controls = format!("{} to resume, {} for the menu", self.describe(InputAction::Pause), self.describe(InputAction::Menu));
+        TEXT.render_text(&controls, vec2(center, middle + 20.0), 0.75, vec3(0.8, 0.8, 0.8), TextAlign::Center);
+    }
+
+    unsafe fn render_level_complete(&self, time: f32) {
+        self.render_scene(time, 0.6);
+        self.render_hud();
+        let center = self.width as f32 / 2.0;
+        let middle = self.height as f32 / 2.0;
+        TEXT.render_text("LEVEL COMPLETE", vec2(center, middle - 40.0), 1.5, vec3(0.2, 1.0, 0.2), TextAlign::Center);
+        TEXT.render_text(&format!("Press {} to continue", self.menu_hint(Key::Enter, GamepadButton::ButtonA)), vec2(center, middle + 20.0), 0.75, vec3(0.8, 0.8, 0.8), TextAlign::Center);
+    }
+
+    unsafe fn render_win(&self, time: f32) {
+        self.render_scene(time, 0.75 + 0.25 * (time * 2.0).sin());
+        let center = self.width as f32 / 2.0;
+        let middle = self.height as f32 / 2.0;
+        TEXT.render_text("YOU WON!!!", vec2(center, middle - 60.0), 2.0, vec3(0.0, 1.0, 0.0), TextAlign::Center);
+        TEXT.render_text(&format!("Final score: {}", self.score()), vec2(center, middle), 1.0, vec3(1.0, 1.0, 1.0), TextAlign::Center);
+        if self.playback.is_none() {
+            let controls = format!(
+                "{} for the menu, {} to watch the replay",
+                self.menu_hint(Key::Enter, GamepadButton::ButtonA), self.menu_hint(Key::R, REPLAY_BUTTON)
+            );
+            TEXT.render_text(&controls, vec2(center, middle + 40.0), 0.75, vec3(1.0, 1.0, 0.0), TextAlign::Center);
+        }
+    }
+
+    unsafe fn render_game_over(&self, time: f32) {
+        self.render_scene(time, 0.25);
+        let center = self.width as f32 / 2.0;
+        let middle = self.height as f32 / 2.0;
+        TEXT.render_text("GAME OVER", vec2(center, middle - 60.0), 2.0, vec3(1.0, 0.2, 0.2), TextAlign::Center);
+        TEXT.render_text(&format!("Score: {}", self.score()), vec2(center, middle), 1.0, vec3(1.0, 1.0, 1.0), TextAlign::Center);
+        if self.playback.is_none() {
+            let controls = format!(
+                "{} to retry, {} to watch the replay, {} for the menu",
+                self.menu_hint(Key::Enter, GamepadButton::ButtonA), self.menu_hint(Key::R, REPLAY_BUTTON), self.describe(InputAction::Menu)
+            );
+            TEXT.render_text(&controls, vec2(center, middle + 40.0), 0.75, vec3(0.8, 0.8, 0.8), TextAlign::Center);
+        }
+    }
+
+    unsafe fn render_bindings(&self, time: f32) {
+        self.render_scene(time, 0.3);
+        let center = self.width as f32 / 2.0;
+        let top = self.height as f32 / 4.0;
+        TEXT.render_text("CONTROLS", vec2(center, top - 60.0), 1.5, vec3(1.0, 1.0, 1.0), TextAlign::Center);
+        for (index, action) in InputAction::ALL.iter().enumerate() {
+            let selected = index == self.binding_selection;
+            let keys = if selected && self.capturing_key {
+                "press a key or button...".to_string()
+            } else {
+                self.describe(*action)
+            };
+            let color = if selected { vec3(1.0, 1.0, 0.0) } else { vec3(0.8, 0.8, 0.8) };
+            let y = top + 30.0 * index as f32;
+            TEXT.render_text(action.label(), vec2(center - 20.0, y), 1.0, color, TextAlign::Right);
+            TEXT.render_text(&keys, vec2(center + 20.0, y), 1.0, color, TextAlign::Left);
+        }
+        let help = if self.capturing_key {
+            "ESC to cancel".to_string()
+        } else {
+            format!(
+                "{}/{} to choose, {} to add a key or button, {} to clear, {} for the defaults, {} to go back",
+                self.menu_hint(Key::Up, GamepadButton::ButtonDpadUp), self.menu_hint(Key::Down, GamepadButton::ButtonDpadDown),
+                self.menu_hint(Key::Enter, GamepadButton::ButtonA), self.menu_hint(Key::Backspace, GamepadButton::ButtonX),
+                self.menu_hint(Key::Delete, GamepadButton::ButtonY), self.menu_hint(Key::Escape, GamepadButton::ButtonB)
+            )
+        };
+        TEXT.render_text(&help, vec2(center, top + 30.0 * InputAction::ALL.len() as f32 + 30.0), 0.5, vec3(0.8, 0.8, 0.8), TextAlign::Center);
+    }
+
+    unsafe fn render_editor(&self, time: f32) {
+        self.render_scene(time, 1.0);
+
+        let resources = RESOURCES.lock().unwrap();
+        let white = resources.get_texture("white");
+        let level = &self.sim.levels[self.sim.actual_level];
+        let (columns, rows) = level.grid_size();
+        let unit = level.unit_size();
+        let grid_size = vec2(unit.x * columns as f32, unit.y * rows as f32);
+        // grid lines
+        let line_color = vec3(0.4, 0.4, 0.4);
+        for column in 0..=columns {
+            RENDERER.draw_sprite(&white, vec2(unit.x * column as f32, 0.0), vec2(1.0, grid_size.y), 0.0, line_color);
+        }
+        for row in 0..=rows {
+            RENDERER.draw_sprite(&white, vec2(0.0, unit.y * row as f32), vec2(grid_size.x, 1.0), 0.0, line_color);
+        }
+        // tile under the cursor
+        if let Some((column, row)) = self.editor_hover {
+            draw_outline(&white, vec2(unit.x * column as f32, unit.y * row as f32), unit, vec3(1.0, 1.0, 0.0));
+        }
+
+        // palette to pick the brush from
+        let brushes = Editor::brushes(&level.palette);
+        for (i, code) in brushes.iter().enumerate() {
+            let slot = Editor::palette_slot(i, self.height);
+            match find_brick_type(&level.palette, *code) {
+                Some(brick_type) => {
+                    let texture = resources.get_texture(&brick_type.texture);
+                    RENDERER.draw_sprite(&texture, slot, PALETTE_SLOT_SIZE, 0.0, brick_type.color());
+                },
+                None => RENDERER.draw_sprite(&white, slot, PALETTE_SLOT_SIZE, 0.0, vec3(0.1, 0.1, 0.1)),
             }
+            if *code == self.editor.brush {
+                draw_outline(&white, slot, PALETTE_SLOT_SIZE, vec3(1.0, 1.0, 1.0));
+            }
+        }
+        drop(resources);
+        for (i, code) in brushes.iter().enumerate() {
+            let slot = Editor::palette_slot(i, self.height);
+            TEXT.render_text(&code.to_string(), vec2(slot.x + PALETTE_SLOT_SIZE.x / 2.0, slot.y + 2.0), 0.75, vec3(1.0, 1.0, 1.0), TextAlign::Center);
+        }
+
+        // status
+        let white_text = vec3(1.0, 1.0, 1.0);
+        let grey_text = vec3(0.8, 0.8, 0.8);
+        let info_y = self.height as f32 - PALETTE_SLOT_SIZE.y - PALETTE_SLOT_SPACING * 2.0 - TEXT.line_height * 1.5;
+        TEXT.render_text(&format!("{} ({}) - {} x {}", self.level_name(), level.file_path, columns, rows), vec2(5.0, info_y), 0.75, white_text, TextAlign::Left);
+        TEXT.render_text("LMB paint, RMB erase, Q/E brush, arrows resize, G/H generate, T play-test, S save, F save as TOML, ESC exit", vec2(5.0, info_y + TEXT.line_height * 0.75), 0.5, grey_text, TextAlign::Left);
+        if self.editor.message_time > 0.0 {
+            TEXT.render_text(&self.editor.message, vec2(self.width as f32 / 2.0, self.height as f32 / 2.0 + 40.0), 1.0, vec3(1.0, 0.8, 0.2), TextAlign::Center);
         }
+    }
 
-        // also check collisions on PowerUps and if so, activate them
-        for power_up in &mut self.power_ups {
+    // draws the status overlay on top of the post-processed scene
+    unsafe fn render_hud(&self) {
+        let white = vec3(1.0, 1.0, 1.0);
+        // lives, as one small ball per life left
+        TEXT.render_text("Lives:", vec2(5.0, 5.0), 0.75, white, TextAlign::Left);
+        let resources = RESOURCES.lock().unwrap();
+        let life_texture = resources.get_texture("face");
+        let life_size = vec2(16.0, 16.0);
+        let lives_x = 10.0 + TEXT.measure("Lives:", 0.75);
+        for i in 0..self.sim.lives {
+            let position = vec2(lives_x + i as f32 * (life_size.x + 4.0), 6.0);
+            RENDERER.draw_sprite(&life_texture, position, life_size, 0.0, white);
+        }
+        // level name
+        TEXT.render_text(&self.level_name(), vec2(self.width as f32 / 2.0, 5.0), 0.75, white, TextAlign::Center);
+        // score and combo
+        let right = self.width as f32 - 5.0;
+        TEXT.render_text(&format!("Score: {}", self.score()), vec2(right, 5.0), 0.75, white, TextAlign::Right);
+        let multiplier = self.sim.score.multiplier();
+        if multiplier > 1 {
+            TEXT.render_text(&format!("Combo x{}", multiplier), vec2(right, 5.0 + TEXT.line_height * 0.75), 0.75, vec3(1.0, 0.8, 0.2), TextAlign::Right);
+        }
+        // active effects below the lives, each with a bar counting down its time
+        let white_texture = resources.get_texture("white");
+        let mut y = 10.0 + TEXT.line_height * 0.75;
+        for effect in self.active_effects() {
+            // flash when about to run out
+            let visible = effect.duration > EFFECT_WARNING_TIME || (effect.duration * 8.0).sin() > 0.0;
+            if visible {
+                RENDERER.draw_sprite(&resources.get_texture(&effect.kind.texture_name()), vec2(5.0, y), EFFECT_ICON_SIZE, 0.0, effect.kind.color);
+            }
+            let bar_position = vec2(5.0, y + EFFECT_ICON_SIZE.y + 2.0);
+            let left = (effect.duration / effect.kind.duration).clamp(0.0, 1.0);
+            RENDERER.draw_sprite(&white_texture, bar_position, vec2(EFFECT_ICON_SIZE.x, 3.0), 0.0, vec3(0.2, 0.2, 0.2));
+            RENDERER.draw_sprite(&white_texture, bar_position, vec2(EFFECT_ICON_SIZE.x * left, 3.0), 0.0, effect.kind.color);
+            if effect.layers > 1 {
+                TEXT.render_text(&format!("x{}", effect.layers), vec2(10.0 + EFFECT_ICON_SIZE.x, y), 0.5, white, TextAlign::Left);
+            }
+            y += EFFECT_ICON_SIZE.y + 10.0;
+        }
+    }
+
+    // name from the pack manifest, else from the level file
+    fn level_name(&self) -> String {
+        let name = self.pack.levels[self.sim.actual_level].name.as_ref()
+            .or(self.sim.levels[self.sim.actual_level].info.name.as_ref());
+        match name {
+            Some(name) => name.clone(),
+            None => format!("Level {}", self.sim.actual_level + 1)
+        }
+    }
+
+    fn is_unlocked(&self, level: usize) -> bool {
+        match self.pack.levels[level].unlock {
+            Unlock::Progress => level <= self.progress.highest_level,
+            Unlock::Always => true,
+        }
+    }
+
+    // next unlocked level in the given direction, wrapping around
+    fn cycle_level(&self, forward: bool) -> usize {
+        let count = self.sim.levels.len();
+        let mut level = self.sim.actual_level;
+        for _ in 0..count {
+            level = if forward { (level + 1) % count } else { (level + count - 1) % count };
+            if self.is_unlocked(level) {
+                return level;
+            }
+        }
+        self.sim.actual_level
+    }
+
+    // draws the level, paddle, power-ups and ball through the post-processor
+    unsafe fn render_scene(&self, time: f32, brightness: f32) {
+        // mirror the effects requested by the simulation
+        POST_PROCESSOR.confuse = self.sim.effects.confuse;
+        POST_PROCESSOR.chaos = self.sim.effects.chaos;
+        POST_PROCESSOR.shake = self.sim.effects.shake;
+        POST_PROCESSOR.brightness = brightness;
+
+        // begin rendering to postprocessing framebuffer
+        POST_PROCESSOR.begin_render();
+
+        let resources = RESOURCES.lock().unwrap();
+        let alpha = self.timestep.alpha();
+        // Draw background
+        let level = &self.sim.levels[self.sim.actual_level];
+        let background_tex = resources.get_texture(level.info.background.as_deref().unwrap_or("background"));
+        RENDERER.draw_sprite(&background_tex, vec2(0.0, 0.0), vec2(self.width as f32, self.height as f32), 0.0, vec3(1.0, 1.0, 1.0));
+        // Draw level
+        render_level(level, &resources);
+        // draw player
+        let mut player = self.sim.player;
+        player.position = self.previous.player.lerp(player.position, alpha);
+        draw_object(&player, &resources.get_texture("paddle"));
+        // the laser paddle shows its guns at both ends
+        if self.sim.laser {
+            let white = resources.get_texture("white");
+            for x in [player.position.x + 8.0, player.position.x + player.size.x - 14.0].iter() {
+                RENDERER.draw_sprite(&white, vec2(*x, player.position.y - 6.0), vec2(6.0, 6.0), 0.0, vec3(1.0, 0.3, 0.3));
+            }
+        }
+        // draw laser bolts
+        for laser in self.sim.lasers.iter() {
+            let mut laser = *laser;
+            interpolate(&mut laser, &self.previous.lasers, alpha);
+            draw_object(&laser, &resources.get_texture("white"));
+        }
+        // draw the shield along the bottom of the screen
+        if self.sim.shield {
+            RENDERER.draw_sprite(&resources.get_texture("white"), vec2(0.0, self.height as f32 - 4.0), vec2(self.width as f32, 4.0), 0.0, vec3(0.3, 0.7, 0.9));
+        }
+        // draw powerups
+        for power_up in self.sim.power_ups.iter() {
             if !power_up.game_object.destroyed {
-                // first check if powerup passed bottom edge, if so: keep as inactive and destroy
-                if power_up.game_object.position.y >= self.height as f32 {
-                    power_up.game_object.destroyed = true;
-                }
+                let mut object = power_up.game_object;
+                interpolate(&mut object, &self.previous.power_ups, alpha);
+                let texture = resources.get_texture(&power_up.kind.texture_name());
+                draw_object(&object, &texture);
+            }
+        }
+        // draw particles	
+        PARTICLE_GENERATOR.draw();
+        // draw balls
+        for ball in self.sim.balls.iter() {
+            let mut ball = *ball;
+            interpolate(&mut ball.game_object, &self.previous.balls, alpha);
+            draw_object(&ball.game_object, &resources.get_texture("face"));
+        }
 
-                if check_square_collision(&self.player, &power_up.game_object) {
-                    // collided with player, now activate powerup
-                    let pw_type = power_up.pw_type.as_str();
-                    match pw_type {
-                        "sticky" => {
-                            self.ball.sticky = true;
-                            self.player.color = vec3(1.0, 0.5, 1.0);
-                        }
-                        "speed" => {
-                            self.ball.game_object.velocity *= 1.2;
-                        }
-                        "passthrough" => {
-                            self.ball.passthrough = true;
-                            self.ball.game_object.color = vec3(1.0, 0.5, 0.5);
-                        }
-                        "increase" => {
-                            self.player.size.x += 50.0;
-                        }
-                        "confuse" => unsafe {
-                            if !POST_PROCESSOR.chaos {
-                                POST_PROCESSOR.confuse = true;
-                            }
-                        }
-                        "chaos" => unsafe {
-                            if !POST_PROCESSOR.confuse {
-                                POST_PROCESSOR.chaos = true;
-                            }
-                        }
-                        &_ => {}
-                    }
-                    power_up.game_object.destroyed = true;
-                    power_up.activated = true;
-                }
+        // end rendering to postprocessing framebuffer
+        POST_PROCESSOR.end_render();
+        // render postprocessing quad
+        POST_PROCESSOR.render(time);
+    }
+
+    pub fn process_input(&mut self, window: &glfw::Window) {
+        // forget keys that have been released since the last frame
+        self.keys_processed.retain(|key| window.get_key(*key) == Action::Press);
+        // gamepads can be plugged in and out at any time
+        if self.gamepad.poll(&window.glfw) {
+            self.gamepad_message = match self.gamepad.joystick {
+                Some(_) => format!("{} connected", self.gamepad.name),
+                None => "Gamepad disconnected".to_string(),
+            };
+            self.gamepad_message_time = GAMEPAD_MESSAGE_TIME;
+            // losing the gamepad mid-game pauses it
+            if self.gamepad.joystick.is_none() && self.state == GameState::GameActive && self.playback.is_none() {
+                self.set_state(GameState::GamePaused);
+                return;
             }
         }
 
-        // check collisions for player pad (unless stuck)
-        let pad_collision: Collision = check_circle_collision(&self.ball, &self.player);
-        if !self.ball.stuck && pad_collision.0 {
-            // check where it hit the board, and change velocity based on where it hit the board
-            let center_board: f32 = self.player.position.x + self.player.size.x / 2.0;
-            let distance: f32 = (self.ball.game_object.position.x + self.ball.radius) - center_board;
-            let percentage: f32 = distance / (self.player.size.x / 2.0);
-            // then move accordingly
-            let strength = 2.0;
-            let old_velocity = self.ball.game_object.velocity;
-            self.ball.game_object.velocity.x = INITIAL_BALL_VELOCITY.x * percentage * strength; 
-            //self.ball.game_object.velocity.y = -self.ball.game_object.velocity.y;
-            self.ball.game_object.velocity = self.ball.game_object.velocity.normalize() * length(old_velocity); // keep speed consistent over both axes (multiply by length of old velocity, so total strength is not changed)
-            // fix sticky paddle
-            self.ball.game_object.velocity.y = -1.0 * self.ball.game_object.velocity.y.abs();
+        // while a replay plays, the controls only control the playback
+        let replay_states = [GameState::GameActive, GameState::GameLevelComplete, GameState::GameWin, GameState::GameOver];
+        if self.playback.is_some() && replay_states.contains(&self.state) {
+            self.process_replay_input(window);
+            return;
+        }
+
+        match self.state {
+            GameState::GameMenu => self.process_menu_input(window),
+            GameState::GameLevelSelect => self.process_level_select_input(window),
+            GameState::GameActive => self.process_active_input(window),
+            GameState::GamePaused => self.process_paused_input(window),
+            GameState::GameLevelComplete => self.process_level_complete_input(window),
+            GameState::GameWin => self.process_win_input(window),
+            GameState::GameOver => self.process_game_over_input(window),
+            GameState::GameEditor => self.process_editor_input(window),
+            GameState::GameBindings => self.process_bindings_input(window),
+        }
+    }
+
+    fn process_menu_input(&mut self, window: &glfw::Window) {
+        if self.confirm_pressed(window) {
+            self.set_state(GameState::GameActive);
+        } else if self.menu_pressed(window, Key::L, LEVEL_SELECT_BUTTON) {
+            self.set_state(GameState::GameLevelSelect);
+        } else if self.menu_pressed(window, Key::E, EDITOR_BUTTON) {
+            self.set_state(GameState::GameEditor);
+        } else if self.menu_pressed(window, Key::R, REPLAY_BUTTON) {
+            self.watch_last_replay();
+        } else if self.menu_pressed(window, Key::B, BINDINGS_BUTTON) {
+            self.set_state(GameState::GameBindings);
+        }
+    }
 
-            // if Sticky powerup is activated, also stick ball to paddle once new velocity vectors were calculated
-            self.ball.stuck = self.ball.sticky;
+    fn process_level_select_input(&mut self, window: &glfw::Window) {
+        // only unlocked levels can be selected
+        if self.action_pressed(window, InputAction::MoveRight) {
+            let level = self.cycle_level(true);
+            self.sim.set_level(level);
+        }
+        if self.action_pressed(window, InputAction::MoveLeft) {
+            let level = self.cycle_level(false);
+            self.sim.set_level(level);
+        }
+        if self.confirm_pressed(window) {
+            self.set_state(GameState::GameActive);
+        } else if self.action_pressed(window, InputAction::Menu) {
+            self.set_state(GameState::GameMenu);
         }
     }
 
-    fn update_power_ups(&mut self, dt: f32) {
-        let mut power_up_list = self.power_ups.clone();
+    fn process_active_input(&mut self, window: &glfw::Window) {
+        if self.editor.play_testing && self.key_pressed(window, Key::T) {
+            self.set_state(GameState::GameEditor);
+            return;
+        }
+        if self.action_pressed(window, InputAction::Pause) {
+            self.set_state(GameState::GamePaused);
+            return;
+        }
 
-        for power_up in &mut self.power_ups {
-            power_up.game_object.position += power_up.game_object.velocity;
+        // sampled once per frame, applied on every simulation step of that frame
+        // keys and buttons move the paddle at full speed, the stick as far as it is pushed
+        let left = self.action_held(window, InputAction::MoveLeft) as i32 as f32;
+        let right = self.action_held(window, InputAction::MoveRight) as i32 as f32;
+        let steer = right - left + self.gamepad.stick(self.settings.gamepad_deadzone);
+        self.input = PlayerInput {
+            steer: steer.clamp(-1.0, 1.0),
+            launch: self.action_held(window, InputAction::Launch),
+        };
+    }
 
-            if power_up.activated {
-                power_up.duration -= dt;
+    fn process_paused_input(&mut self, window: &glfw::Window) {
+        if self.action_pressed(window, InputAction::Pause) {
+            self.set_state(GameState::GameActive);
+        } else if self.action_pressed(window, InputAction::Menu) {
+            self.set_state(GameState::GameMenu);
+        }
+    }
 
-                if power_up.duration <= 0.0 {
-                    // remove powerup from list (will later be removed)
-                    power_up.activated = false;
+    fn process_level_complete_input(&mut self, window: &glfw::Window) {
+        // skip the rest of the transition
+        if self.confirm_pressed(window) {
+            self.set_state(GameState::GameActive);
+        }
+    }
 
-                    if let Some(pos) = power_up_list.iter().position(|pu| (pu.pw_type == power_up.pw_type) && pu.activated ) {
-                        power_up_list[pos].activated = false;
-                    }
+    fn process_win_input(&mut self, window: &glfw::Window) {
+        if self.confirm_pressed(window) {
+            self.set_state(GameState::GameMenu);
+        } else if self.menu_pressed(window, Key::R, REPLAY_BUTTON) {
+            self.watch_last_replay();
+        }
+    }
 
-                    // deactivate effects
-                    if power_up.pw_type == "sticky" {
-                        if !is_other_power_up_active(&power_up_list, "sticky".to_string()) {
-                            self.ball.sticky = false;
-                            self.player.color = vec3(1.0, 1.0, 1.0);
-                        }
-                    }
-                    if power_up.pw_type == "speed" {
-                        if !is_other_power_up_active(&power_up_list, "speed".to_string()) {
-                            self.ball.game_object.velocity /= 1.2;
-                        }
-                    }
-                    if power_up.pw_type == "passthrough" {
-                        if !is_other_power_up_active(&power_up_list, "passthrough".to_string()) {
-                            self.ball.passthrough = false;
-                            self.ball.game_object.color = vec3(1.0, 1.0, 1.0);
-                        }
-                    }
-                    if power_up.pw_type == "increase" {
-                        if !is_other_power_up_active(&power_up_list, "increase".to_string()) {
-                            self.player.size.x -= 50.0;
-                        }
-                    }
-                    if power_up.pw_type == "confuse" {
-                        if !is_other_power_up_active(&power_up_list, "confuse".to_string()) {
-                            unsafe {
-                                POST_PROCESSOR.confuse = false;
-                            }
-                        }
-                    }
-                    if power_up.pw_type == "chaos" {
-                        if !is_other_power_up_active(&power_up_list, "chaos".to_string()) {
-                            unsafe {
-                                POST_PROCESSOR.chaos = false;
-                            }
-                        }
-                    }
+    fn process_game_over_input(&mut self, window: &glfw::Window) {
+        if self.confirm_pressed(window) {
+            self.set_state(GameState::GameActive);
+        } else if self.menu_pressed(window, Key::R, REPLAY_BUTTON) {
+            self.watch_last_replay();
+        } else if self.action_pressed(window, InputAction::Menu) {
+            self.set_state(GameState::GameMenu);
+        }
+    }
+
+    // the rebinding screen navigates with fixed keys and buttons, so no binding can lock the player out of it
+    fn process_bindings_input(&mut self, window: &glfw::Window) {
+        let action = InputAction::ALL[self.binding_selection];
+        if self.capturing_key {
+            if self.key_pressed(window, Key::Escape) {
+                self.capturing_key = false;
+                return;
+            }
+            let pressed = KEY_NAMES.iter().map(|(key, _)| *key).find(|key| self.key_pressed(window, *key));
+            if let Some(key) = pressed {
+                self.bindings.bind(action, key);
+                self.capturing_key = false;
+            } else if let Some(button) = self.gamepad.any_button_pressed() {
+                self.bindings.bind_button(action, button);
+                self.capturing_key = false;
+            }
+            return;
+        }
+
+        if self.menu_pressed(window, Key::Down, GamepadButton::ButtonDpadDown) {
+            self.binding_selection = (self.binding_selection + 1) % InputAction::ALL.len();
+        }
+        if self.menu_pressed(window, Key::Up, GamepadButton::ButtonDpadUp) {
+            self.binding_selection = (self.binding_selection + InputAction::ALL.len() - 1) % InputAction::ALL.len();
+        }
+        if self.menu_pressed(window, Key::Enter, GamepadButton::ButtonA) {
+            self.capturing_key = true;
+        } else if self.menu_pressed(window, Key::Backspace, GamepadButton::ButtonX) {
+            self.bindings.clear(action);
+        } else if self.menu_pressed(window, Key::Delete, GamepadButton::ButtonY) {
+            self.bindings.reset();
+        } else if self.menu_pressed(window, Key::Escape, GamepadButton::ButtonB) {
+            self.set_state(GameState::GameMenu);
+        }
+    }
+
+    fn process_replay_input(&mut self, window: &glfw::Window) {
+        if self.action_pressed(window, InputAction::Menu) {
+            self.stop_replay();
+            return;
+        }
+        let speeds = [(Key::W, GamepadButton::ButtonDpadUp, true), (Key::S, GamepadButton::ButtonDpadDown, false)];
+        for (key, button, faster) in speeds.iter() {
+            if self.menu_pressed(window, *key, *button) {
+                if let Some(playback) = &mut self.playback {
+                    playback.change_speed(*faster);
                 }
             }
         }
+        if self.menu_pressed(window, Key::Left, GamepadButton::ButtonDpadLeft) {
+            self.scrub_replay(-SCRUB_TIME);
+        }
+        if self.menu_pressed(window, Key::Right, GamepadButton::ButtonDpadRight) {
+            self.scrub_replay(SCRUB_TIME);
+        }
+    }
 
-        // self.power_ups.retain(|pu| pu.duration > 0.0);
-        self.power_ups.retain(|pu| pu.duration > 0.0);
+    // plays the last game back, from memory or else from the saved replay
+    fn watch_last_replay(&mut self) {
+        let replay = self.last_replay.clone().or_else(|| Replay::load(REPLAY_PATH));
+        if let Some(replay) = replay {
+            self.start_replay(replay);
+        }
     }
-}
 
-fn spawn_power_ups(pos: Vector2<f32>) -> Option<PowerUp> {
-    let resources = RESOURCES.lock().unwrap();
-
-    if power_up_should_spawn(75) {
-        Some(PowerUp::new(pos, vec3(1.0, 0.5, 1.0), resources.get_texture("powerup_sticky"), "sticky", 15.0, false))
-    } else if power_up_should_spawn(75) {
-        Some(PowerUp::new(pos, vec3(0.5, 0.5, 1.0), resources.get_texture("powerup_speed"), "speed", 15.0, false))
-    } else if power_up_should_spawn(75) {
-        Some(PowerUp::new(pos, vec3(0.5, 1.0, 0.5), resources.get_texture("powerup_passthrough"), "passthrough", 10.0, false))
-    } else if power_up_should_spawn(75) {
-        Some(PowerUp::new(pos, vec3(1.0, 0.6, 0.4), resources.get_texture("powerup_increase"), "increase", 15.0, false))
-    } else if power_up_should_spawn(15) {
-        Some(PowerUp::new(pos, vec3(1.0, 0.3, 0.3), resources.get_texture("powerup_confuse"), "confuse", 15.0, false))
-    } else if power_up_should_spawn(15) {
-        Some(PowerUp::new(pos, vec3(0.9, 0.25, 0.25), resources.get_texture("powerup_chaos"), "chaos", 15.0, false))
-    } else {
-        None
+    fn process_editor_input(&mut self, window: &glfw::Window) {
+        // cursor position in game coordinates
+        let (cursor_x, cursor_y) = window.get_cursor_pos();
+        let (window_width, window_height) = window.get_size();
+        let cursor = vec2(
+            cursor_x as f32 * self.width as f32 / window_width.max(1) as f32,
+            cursor_y as f32 * self.height as f32 / window_height.max(1) as f32
+        );
+        let left_button = window.get_mouse_button(MouseButton::Button1) == Action::Press;
+        let right_button = window.get_mouse_button(MouseButton::Button2) == Action::Press;
+
+        let level = &mut self.sim.levels[self.sim.actual_level];
+        self.editor_hover = level.tile_at(cursor);
+        // pick a brush from the palette or paint the grid
+        let brushes = Editor::brushes(&level.palette);
+        if let Some(index) = Editor::palette_slot_at(cursor, brushes.len(), self.height) {
+            if left_button {
+                self.editor.brush = brushes[index];
+            }
+        } else if let Some((column, row)) = self.editor_hover {
+            if left_button {
+                level.set_tile(column, row, self.editor.brush);
+            } else if right_button {
+                level.set_tile(column, row, 0);
+            }
+        }
+
+        if self.key_pressed(window, Key::Q) {
+            self.editor.cycle_brush(&self.sim.levels[self.sim.actual_level].palette, false);
+        }
+        if self.key_pressed(window, Key::E) {
+            self.editor.cycle_brush(&self.sim.levels[self.sim.actual_level].palette, true);
+        }
+
+        // resize the grid, adding or removing columns on the right and rows at the bottom
+        let (columns, rows) = self.sim.levels[self.sim.actual_level].grid_size();
+        let mut size = (columns, rows);
+        if self.key_pressed(window, Key::Right) {
+            size.0 += 1;
+        }
+        if self.key_pressed(window, Key::Left) {
+            size.0 -= cmp::min(size.0, 1);
+        }
+        if self.key_pressed(window, Key::Down) {
+            size.1 += 1;
+        }
+        if self.key_pressed(window, Key::Up) {
+            size.1 -= cmp::min(size.1, 1);
+        }
+        if size != (columns, rows) {
+            self.sim.levels[self.sim.actual_level].resize(size.0, size.1);
+        }
+
+        // replace the layout with a generated one of the same size, G with a new seed
+        // and H with the next pattern family for the same seed
+        let generate_seed = self.key_pressed(window, Key::G);
+        let generate_pattern = self.key_pressed(window, Key::H);
+        if generate_seed || generate_pattern {
+            if generate_seed {
+                // not drawn from the game's own randomness, which a play-test replays from its seed
+                self.editor.generator.seed = rand::random::<u32>() as u64;
+            } else {
+                self.editor.cycle_pattern();
+            }
+            self.generate_level();
+        }
+
+        if self.key_pressed(window, Key::S) {
+            let file_path = self.sim.levels[self.sim.actual_level].file_path.clone();
+            self.save_level(&file_path);
+        } else if self.key_pressed(window, Key::F) {
+            self.save_level_as_toml();
+        } else if self.key_pressed(window, Key::T) {
+            self.set_state(GameState::GameActive);
+        } else if self.menu_pressed(window, Key::Escape, GamepadButton::ButtonB) {
+            self.set_state(GameState::GameMenu);
+        }
     }
-}
 
-fn power_up_should_spawn(chance: u32) -> bool {
-    let mut rng = rand::thread_rng();
-    let random: u32 = rng.gen::<u32>() % chance;
-    random == 0
-}
+    fn generate_level(&mut self) {
+        let level = &mut self.sim.levels[self.sim.actual_level];
+        let (columns, rows) = level.grid_size();
+        self.editor.generator.columns = columns;
+        self.editor.generator.rows = rows;
+        level.set_tiles(level_generator::generate(&self.editor.generator));
+        self.editor.show_message(format!("Generated a {} layout from seed {}", self.editor.generator.pattern.name(), self.editor.generator.seed));
+    }
 
-fn is_other_power_up_active(power_ups: &Vec<PowerUp>, pw_type: String) -> bool {
-    // Check if another PowerUp of the same type is still active
-    // in which case we don't disable its effect (yet)
-    for power_up in power_ups {
-        if power_up.activated && power_up.pw_type == pw_type {
-            return true;
+    fn save_level(&mut self, file_path: &str) -> bool {
+        let level = &mut self.sim.levels[self.sim.actual_level];
+        match level.save(file_path) {
+            Ok(()) => {
+                // further saves (and reloads) go to the new file
+                level.file_path = file_path.to_string();
+                self.editor.show_message(format!("Saved to {}", file_path));
+                true
+            },
+            Err(error) => {
+                println!("ERROR::LEVEL: Failed to save {}: {}", file_path, error);
+                self.editor.show_message(format!("Failed to save {}", file_path));
+                false
+            }
         }
     }
 
-    return false;
+    // converts the current level to TOML in place: the TOML file replaces the old one, both on
+    // disk and in the pack manifest, so the level isn't discovered twice on the next start
+    fn save_level_as_toml(&mut self) {
+        let file_path = self.sim.levels[self.sim.actual_level].file_path.clone();
+        let toml_path = Path::new(&file_path).with_extension("toml").to_string_lossy().to_string();
+        if toml_path == file_path {
+            self.save_level(&file_path);
+            return;
+        }
+        if Path::new(&toml_path).exists() {
+            self.editor.show_message(format!("{} already exists", toml_path));
+            return;
+        }
+        if !self.save_level(&toml_path) {
+            return;
+        }
+
+        let entry = &mut self.pack.levels[self.sim.actual_level];
+        let old_file = entry.file.clone();
+        entry.file = Path::new(&old_file).with_extension("toml").to_string_lossy().to_string();
+        if let Err(error) = LevelPack::rename_in_manifest(LEVEL_DIRECTORY, &old_file, &entry.file) {
+            println!("ERROR::LEVEL: Failed to update the manifest of {}: {}", LEVEL_DIRECTORY, error);
+        }
+        if let Err(error) = fs::remove_file(&file_path) {
+            println!("ERROR::LEVEL: Failed to remove {}: {}", file_path, error);
+        }
+    }
+
+    // reloads the current level from its file, dropping any changes made to it
+    fn reload_level(&mut self) {
+        let level = &mut self.sim.levels[self.sim.actual_level];
+        let file_path = level.file_path.clone();
+        let (level_width, level_height) = (level.level_width, level.level_height);
+        if let Err(error) = level.load(&file_path, level_width, level_height) {
+            println!("ERROR::LEVEL: Failed to reload {}: {}", file_path, error);
+        }
+    }
+
+    // true only on the frame a key goes down, so menu actions don't repeat while it is held
+    fn key_pressed(&mut self, window: &glfw::Window, key: Key) -> bool {
+        window.get_key(key) == Action::Press && self.keys_processed.insert(key)
+    }
+
+    // true only on the frame one of the keys or buttons bound to an action goes down
+    fn action_pressed(&mut self, window: &glfw::Window, action: InputAction) -> bool {
+        let keys = self.bindings.keys(action).to_vec();
+        let buttons = self.bindings.buttons(action).to_vec();
+        keys.iter().any(|key| self.key_pressed(window, *key))
+            || buttons.iter().any(|button| self.gamepad.button_pressed(*button))
+    }
+
+    // true while any key or button bound to an action is down, those then count as
+    // handled so holding them doesn't also act on the screen that follows
+    fn action_held(&mut self, window: &glfw::Window, action: InputAction) -> bool {
+        let mut held = false;
+        for key in self.bindings.keys(action) {
+            if window.get_key(*key) == Action::Press {
+                self.keys_processed.insert(*key);
+                held = true;
+            }
+        }
+        for button in self.bindings.buttons(action) {
+            held |= self.gamepad.button_held(*button);
+        }
+        held
+    }
+
+    // accepts whatever a menu screen offers, with ENTER, the A button or the launch action
+    fn confirm_pressed(&mut self, window: &glfw::Window) -> bool {
+        self.menu_pressed(window, Key::Enter, GamepadButton::ButtonA) || self.action_pressed(window, InputAction::Launch)
+    }
+
+    // true only on the frame a fixed menu key or its gamepad button goes down
+    fn menu_pressed(&mut self, window: &glfw::Window, key: Key, button: GamepadButton) -> bool {
+        self.key_pressed(window, key) || self.gamepad.button_pressed(button)
+    }
+
+    // a fixed menu key as shown on screen, followed by its gamepad button while one is connected
+    fn menu_hint(&self, key: Key, button: GamepadButton) -> String {
+        if self.gamepad.joystick.is_some() {
+            format!("{}/{}", key_name(key), button_name(button))
+        } else {
+            key_name(key).to_string()
+        }
+    }
+
+    // what an action is bound to, as shown on screen
+    fn describe(&self, action: InputAction) -> String {
+        self.bindings.describe(action, self.gamepad.joystick.is_some())
+    }
 }
 
-// AABB - AABB collision
-fn check_square_collision(one: &GameObject, two: &GameObject) -> bool {
-    // collision x-axis?
-    let collision_x = one.position.x + one.size.x >= two.position.x && two.position.x + two.size.x >= one.position.x;
-    // collision y-axis?
-    let collision_y = one.position.y + one.size.y >= two.position.y && two.position.y + two.size.y >= one.position.y;
-    // collision only if on both axes
-    collision_x && collision_y
+unsafe fn draw_object(object: &GameObject, texture: &Texture2D) {
+    RENDERER.draw_sprite(texture, object.position, object.size, object.rotation, object.color);
 }
 
-// AABB - circle collision
-fn check_circle_collision(one: &Ball, two: &GameObject) -> Collision {
-    // get center point circle first 
-    let center = vec2(one.game_object.position.x + one.radius, one.game_object.position.y + one.radius);
-    // calculate AABB info (center, half-extents)
-    let aabb_half_extents = vec2(two.size.x / 2.0, two.size.y / 2.0);
-    let aabb_center = vec2(
-        two.position.x + aabb_half_extents.x, 
-        two.position.y + aabb_half_extents.y
-    );
-    // get difference vector between both centers
-    let difference = center - aabb_center;
-    let clamped = vec2(
-        clamp(difference.x as i32, -aabb_half_extents.x as i32, aabb_half_extents.x as i32),
-        clamp(difference.y as i32, -aabb_half_extents.y as i32, aabb_half_extents.y as i32),    
-    );
-    // add clamped value to AABB_center and we get the value of box closest to circle
-    let closest = aabb_center + clamped;
-    // retrieve vector between center circle and closest point AABB and check if length <= radius
-    let difference = closest - center;
-
-    if length(difference) <= one.radius {
-        (true, vector_direction(difference), difference)
-    } else {
-        (false, Direction::Up, vec2(0.0, 0.0))
+// draws the bricks left in a level, darkening them as they lose hit points
+unsafe fn render_level(level: &GameLevel, resources: &ResourceManager) {
+    for brick in level.bricks.iter().filter(|brick| !brick.game_object.destroyed) {
+        // check block type from level data (2D level array)
+        let brick_type = find_brick_type(&level.palette, brick.code);
+        let mut texture = brick_type.map_or("block", |brick_type| brick_type.texture.as_str());
+        if brick.is_damaged() {
+            if let Some(damaged_texture) = brick_type.and_then(|brick_type| brick_type.damaged_texture.as_ref()) {
+                texture = damaged_texture;
+            }
+        }
+
+        let mut game_object = brick.game_object;
+        game_object.color *= DAMAGE_TINT + (1.0 - DAMAGE_TINT) * brick.health();
+        draw_object(&game_object, &resources.get_texture(texture));
     }
 }
 
-fn clamp(value: i32, min: i32, max: i32) -> f32 {
-    cmp::max(min, cmp::min(max, value)) as f32
+// outlines a rectangle with lines a pixel wide
+unsafe fn draw_outline(texture: &Texture2D, position: Vector2<f32>, size: Vector2<f32>, color: Vector3<f32>) {
+    RENDERER.draw_sprite(texture, position, vec2(size.x, 1.0), 0.0, color);
+    RENDERER.draw_sprite(texture, vec2(position.x, position.y + size.y - 1.0), vec2(size.x, 1.0), 0.0, color);
+    RENDERER.draw_sprite(texture, position, vec2(1.0, size.y), 0.0, color);
+    RENDERER.draw_sprite(texture, vec2(position.x + size.x - 1.0, position.y), vec2(1.0, size.y), 0.0, color);
+}
+
+// applies the settings the simulation depends on
+fn configure_simulation(sim: &mut Simulation, settings: &Settings) {
+    sim.initial_lives = settings.lives;
+    sim.max_lives = cmp::max(settings.max_lives, settings.lives);
+    sim.extra_life_score = settings.extra_life_score;
 }
 
-fn length(vector: Vector2<f32>) -> f32 {
-    (vector.x.powi(2) + vector.y.powi(2)).sqrt()
+// formats seconds as minutes:seconds
+fn clock(seconds: f32) -> String {
+    let seconds = seconds as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
 }
 
-fn vector_direction(target: Vector2<f32>) -> Direction {
-    let compass = vec![
-        vec2(0.0, 1.0), // up
-        vec2(1.0, 0.0), // right
-        vec2(0.0, -1.0), // down
-        vec2(-1.0, 0.0), // left
-    ];
-
-    let mut max = 0.0;
-    let mut best_match: i8 = -1;
-    
-    for (i, _dir) in compass.iter().enumerate() {
-        let dot_product = dot(target.normalize(), compass[i]);
-        if dot_product > max {
-            max = dot_product;
-            best_match = i as i8;
-        }
-    };
-
-    Direction::from_i8(best_match)
+// loads any texture a level refers to that isn't loaded yet, textures named by level files
+// are looked up under resources/textures, by file name
+fn load_level_textures(level: &GameLevel) -> Result<(), String> {
+    let mut resources = RESOURCES.lock().unwrap();
+    for name in level.textures() {
+        if !resources.has_texture(name) {
+            let alpha = name.ends_with(".png");
+            resources.try_load_texture(&format!("resources/textures/{}", name), alpha, name)
+                .map_err(|error| format!("texture {}: {}", name, error))?;
+        }
+    }
+    Ok(())
 }
 
 // fn write_silence<T: Sample>(data: &mut [T], _: &cpal::OutputCallbackInfo) {
@@ -637,4 +1343,4 @@ fn write_silence(data: &mut [f32], _: &cpal::OutputCallbackInfo) {
         *sample = Sample::from(s);
     }
     println!("{:?}", data);
-}
\ No newline at end of file
+}
@@ -1,84 +1,547 @@
-use std::sync::MutexGuard;
-use std::fs::File;
-use std::io::{prelude::*, BufReader};
+use std::fmt;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
 
-use cgmath::{vec2, vec3};
+use cgmath::{vec2, Vector2};
 
+use crate::brick::Brick;
 use crate::game_object::GameObject;
-use crate::lib::sprite_renderer::SpriteRenderer;
-use crate::resource_manager::ResourceManager;
+use crate::level_format::{default_palette, find_brick_type, BrickType, LevelFile, LevelInfo};
 
+// Reasons a level file can fail to load, lines and columns count from 1
+#[derive(Debug)]
+pub enum LevelError {
+    MissingFile(String),
+    // the file exists but isn't a valid level file (e.g. broken TOML)
+    Malformed(String),
+    BadToken { token: String, line: usize, column: usize },
+    RaggedRow { line: usize, expected: usize, found: usize },
+    // no brick has to be destroyed to clear the level (no bricks, or only solid ones)
+    Empty,
+    UnknownBrick { code: u32, line: usize, column: usize },
+    // an entry of a TOML level file whose value makes no sense, like a negative ball speed
+    InvalidValue { key: String, reason: &'static str, line: usize },
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelError::MissingFile(path) => write!(f, "missing file {}", path),
+            LevelError::Malformed(reason) => write!(f, "malformed level: {}", reason),
+            LevelError::BadToken { token, line, column } => {
+                write!(f, "invalid brick code `{}` on line {}, column {}", token, line, column)
+            },
+            LevelError::RaggedRow { line, expected, found } => {
+                write!(f, "row on line {} has {} bricks, expected {}", line, found, expected)
+            },
+            LevelError::Empty => write!(f, "level has no bricks to clear"),
+            LevelError::UnknownBrick { code, line, column } => {
+                write!(f, "unknown brick code {} on line {}, column {}", code, line, column)
+            },
+            LevelError::InvalidValue { key, reason, line } => {
+                write!(f, "invalid `{}` on line {}: {}", key, line, reason)
+            },
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+#[derive(Clone)]
 pub struct GameLevel {
     // level state
-    pub bricks: Vec<GameObject>,
+    pub bricks: Vec<Brick>,
+    pub info: LevelInfo,
+    // brick types the codes of this level map to
+    pub palette: Vec<BrickType>,
+    // file the level was loaded from
+    pub file_path: String,
+    // brick codes as parsed from the file, the bricks are (re)built from these
+    pub tiles: Vec<Vec<u32>>,
+    // area the bricks are laid out in
+    pub level_width: u32,
+    pub level_height: u32,
+    // index of the brick on every tile (row by row), None for empty tiles and destroyed bricks
+    grid: Vec<Option<usize>>,
+}
+
+// Saved state of a level's bricks, restoring it never touches the level file
+#[derive(Clone, Debug)]
+pub struct LevelSnapshot {
+    bricks: Vec<Brick>,
 }
 
 impl GameLevel {
     pub fn new() -> Self {
-        GameLevel { bricks: Vec::new() }
+        GameLevel {
+            bricks: Vec::new(),
+            info: LevelInfo::default(),
+            palette: default_palette(),
+            file_path: String::new(),
+            tiles: Vec::new(),
+            level_width: 0,
+            level_height: 0,
+            grid: Vec::new(),
+        }
+    }
+
+    // loads a level from either a `.toml` level file or a legacy `.lvl` tile grid,
+    // the level is left untouched if the file can't be loaded
+    pub fn load(&mut self, file_path: &str, level_width: u32, level_height: u32) -> Result<(), LevelError> {
+        let contents = fs::read_to_string(file_path).map_err(|error| match error.kind() {
+            ErrorKind::NotFound => LevelError::MissingFile(file_path.to_string()),
+            _ => LevelError::Malformed(error.to_string()),
+        })?;
+
+        let mut info = LevelInfo::default();
+        let mut palette = default_palette();
+        let tile_data = if Path::new(file_path).extension().is_some_and(|ext| ext == "toml") {
+            let level_file: LevelFile = toml::from_str(&contents)
+                .map_err(|error| LevelError::Malformed(error.to_string()))?;
+            validate_level_file(&level_file, &contents)?;
+
+            // brick types of the level override the default ones with the same code
+            for brick_type in level_file.palette {
+                palette.retain(|existing| existing.code != brick_type.code);
+                palette.push(brick_type);
+            }
+            info = level_file.info;
+
+            parse_tiles(&level_file.tiles, tiles_first_line(&contents), &palette)?
+        } else {
+            // legacy levels are nothing but the tile grid
+            parse_tiles(&contents, 1, &palette)?
+        };
+
+        self.info = info;
+        self.palette = palette;
+        self.file_path = file_path.to_string();
+        self.tiles = tile_data;
+        self.level_width = level_width;
+        self.level_height = level_height;
+        self.reset();
+        Ok(())
     }
 
-    pub fn load(&mut self, resources: MutexGuard<ResourceManager<'static>>, file_path: &str, level_width: u32, level_height: u32) {
-        // clear old data
+    // rebuilds every brick as it was when the level was loaded
+    pub fn reset(&mut self) {
         self.bricks.clear();
-        // load from file
-        let mut tile_data: Vec<Vec<u32>> = Vec::new();
+        self.grid.clear();
+        if self.tiles.is_empty() {
+            return;
+        }
+
+        // calculate dimensions
+        let unit = self.unit_size();
 
-        let level_file = File::open(file_path)
-            .unwrap_or_else(|_| panic!("Failed to open {}", file_path));
+        // initialize level tiles based on tileData
+        for (y, row) in self.tiles.iter().enumerate() {
+            for (x, brick) in row.iter().enumerate() {
+                // every code was checked against the palette while parsing
+                let brick_type = match find_brick_type(&self.palette, *brick) {
+                    Some(brick_type) => brick_type,
+                    None => continue
+                };
 
-        let reader = BufReader::new(level_file);
-        for line in reader.lines() {
-            let mut row: Vec<u32> = Vec::new();
-            for number in line.unwrap().split_whitespace() {
-                row.push(number.parse::<u32>().unwrap());
+                let pos = vec2(unit.x * x as f32, unit.y * y as f32);
+                let mut obj = GameObject::new(pos, unit, vec2(0.0, 0.0), brick_type.color());
+                obj.is_solid = brick_type.solid;
+                self.bricks.push(Brick::new(obj, brick_type));
             }
-            tile_data.push(row);
-        }
-
-        if tile_data.len() > 0 {
-            // calculate dimensions
-            let height = tile_data.len();
-            let width = tile_data[0].len();
-            let unit_width = level_width / width as u32;
-            let unit_height = level_height / height as u32;
-
-            // initialize level tiles based on tileData
-            for (y, row) in tile_data.iter().enumerate() {
-                for (x, brick) in row.iter().enumerate() {
-                    if *brick == 0 {
-                        continue;
-                    }
-
-                    let pos = vec2((unit_width * x as u32) as f32, (unit_height * y as u32) as f32);
-                    let size = vec2(unit_width as f32, unit_height as f32);
-                    let color = match *brick {
-                        1 => vec3(0.8, 0.8, 0.7),
-                        2 => vec3(0.2, 0.6, 1.0),
-                        3 => vec3(0.0, 0.7, 0.0),
-                        4 => vec3(0.8, 0.8, 0.4),
-                        5 => vec3(1.0, 0.5, 0.0),
-                        _ => vec3(1.0, 1.0, 1.0)
-                    };
-                    // check block type from level data (2D level array)
-                    let sprite = match *brick {
-                        1 => resources.get_texture("block_solid"),
-                        _ => resources.get_texture("block"),
-                    };
-
-                    let mut obj = GameObject::new(pos, size, vec2(0.0, 0.0), color, sprite);
-                    obj.is_solid = *brick == 1;
-                    self.bricks.push(obj);
+        }
+        self.rebuild_grid();
+    }
+
+    // indices of the bricks left on the tiles overlapping an area
+    pub fn bricks_in(&self, min: Vector2<f32>, max: Vector2<f32>) -> impl Iterator<Item = usize> + '_ {
+        let (columns, rows) = self.grid_size();
+        let unit = self.unit_size();
+        // tiles the area covers, clamped to the grid (and empty if it lies outside of it)
+        let (first_column, last_column) = tile_range(min.x, max.x, unit.x, columns);
+        let (first_row, last_row) = tile_range(min.y, max.y, unit.y, rows);
+
+        (first_row..last_row).flat_map(move |row| {
+            (first_column..last_column).filter_map(move |column| self.grid[row * columns + column])
+        })
+    }
+
+    // damages a brick, taking it out of the grid once destroyed, and returns the points earned
+    pub fn damage_brick(&mut self, index: usize, damage: u32) -> u32 {
+        let points = self.bricks[index].hit(damage);
+        if self.bricks[index].game_object.destroyed {
+            if let Some(tile) = self.tile_index(&self.bricks[index]) {
+                self.grid[tile] = None;
+            }
+        }
+        points
+    }
+
+    // maps every brick that is left to the tile it sits on
+    fn rebuild_grid(&mut self) {
+        let (columns, rows) = self.grid_size();
+        self.grid = vec![None; columns * rows];
+        for (i, brick) in self.bricks.iter().enumerate() {
+            if !brick.game_object.destroyed {
+                if let Some(tile) = self.tile_index(brick) {
+                    self.grid[tile] = Some(i);
                 }
             }
         }
     }
 
-    pub fn draw(&self, renderer: &SpriteRenderer) {
-        for brick in self.bricks.iter() {
-            if !brick.destroyed {
-                brick.draw(renderer);
+    fn tile_index(&self, brick: &Brick) -> Option<usize> {
+        // bricks fill their tile exactly, so their center is always inside it
+        let center = brick.game_object.position + brick.game_object.size / 2.0;
+        self.tile_at(center).map(|(column, row)| row * self.grid_size().0 + column)
+    }
+
+    // number of columns and rows of the tile grid
+    pub fn grid_size(&self) -> (usize, usize) {
+        (self.tiles.first().map_or(0, |row| row.len()), self.tiles.len())
+    }
+
+    // size of a single tile, whole pixels so the bricks line up
+    pub fn unit_size(&self) -> Vector2<f32> {
+        let (columns, rows) = self.grid_size();
+        if columns == 0 || rows == 0 {
+            return vec2(0.0, 0.0);
+        }
+        vec2((self.level_width / columns as u32) as f32, (self.level_height / rows as u32) as f32)
+    }
+
+    // column and row of the tile under a point, if it lies within the grid
+    pub fn tile_at(&self, position: Vector2<f32>) -> Option<(usize, usize)> {
+        let unit = self.unit_size();
+        if position.x < 0.0 || position.y < 0.0 || unit.x == 0.0 || unit.y == 0.0 {
+            return None;
+        }
+        let (column, row) = ((position.x / unit.x) as usize, (position.y / unit.y) as usize);
+        let (columns, rows) = self.grid_size();
+        if column < columns && row < rows {
+            Some((column, row))
+        } else {
+            None
+        }
+    }
+
+    // changes a single tile and rebuilds the bricks to match
+    pub fn set_tile(&mut self, column: usize, row: usize, code: u32) {
+        if self.tiles[row][column] != code {
+            self.tiles[row][column] = code;
+            self.reset();
+        }
+    }
+
+    // replaces the whole tile grid, e.g. with a generated layout
+    pub fn set_tiles(&mut self, tiles: Vec<Vec<u32>>) {
+        self.tiles = tiles;
+        self.reset();
+    }
+
+    // grows or shrinks the grid (to at least a single tile), new tiles are empty
+    pub fn resize(&mut self, columns: usize, rows: usize) {
+        let (columns, rows) = (columns.max(1), rows.max(1));
+        self.tiles.resize(rows, Vec::new());
+        for row in self.tiles.iter_mut() {
+            row.resize(columns, 0);
+        }
+        self.reset();
+    }
+
+    // writes the tile grid back to a file, a `.toml` path gets the metadata and
+    // the brick types that differ from the defaults as well
+    pub fn save(&self, file_path: &str) -> io::Result<()> {
+        let tiles: Vec<String> = self.tiles.iter()
+            .map(|row| row.iter().map(|code| code.to_string()).collect::<Vec<_>>().join(" "))
+            .collect();
+        let mut contents = tiles.join("\n") + "\n";
+
+        if Path::new(file_path).extension().is_some_and(|ext| ext == "toml") {
+            let defaults = default_palette();
+            let level_file = LevelFile {
+                info: self.info.clone(),
+                tiles: contents,
+                palette: self.palette.iter().filter(|brick_type| !defaults.contains(brick_type)).cloned().collect(),
+            };
+            contents = toml::to_string_pretty(&level_file)
+                .map_err(io::Error::other)?;
+        }
+        fs::write(file_path, contents)
+    }
+
+    pub fn snapshot(&self) -> LevelSnapshot {
+        LevelSnapshot { bricks: self.bricks.clone() }
+    }
+
+    pub fn restore(&mut self, snapshot: &LevelSnapshot) {
+        self.bricks.clone_from(&snapshot.bricks);
+        self.rebuild_grid();
+    }
+
+    // a level is completed once every brick that can be destroyed has been,
+    // bricks only a passthrough ball can break are optional
+    pub fn is_completed(&self) -> bool {
+        self.bricks.iter().all(|brick| {
+            brick.game_object.is_solid || brick.passthrough_only || brick.game_object.destroyed
+        })
+    }
+
+    // names of every texture the level draws with
+    pub fn textures(&self) -> Vec<&str> {
+        let mut textures: Vec<&str> = Vec::new();
+        for brick_type in self.palette.iter() {
+            textures.push(&brick_type.texture);
+            if let Some(damaged_texture) = &brick_type.damaged_texture {
+                textures.push(damaged_texture);
+            }
+        }
+        if let Some(background) = &self.info.background {
+            textures.push(background);
+        }
+        textures
+    }
+}
+
+// parses rows of whitespace-separated brick codes (0 is an empty tile), `first_line` is the
+// line of the file the text starts on so errors point at the right place
+fn parse_tiles(text: &str, first_line: usize, palette: &[BrickType]) -> Result<Vec<Vec<u32>>, LevelError> {
+    let mut tile_data: Vec<Vec<u32>> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line_number = first_line + index;
+        let mut row: Vec<u32> = Vec::new();
+        for (column, token) in tokens(line) {
+            let code = token.parse::<u32>().map_err(|_| LevelError::BadToken {
+                token: token.to_string(),
+                line: line_number,
+                column: column,
+            })?;
+            if code != 0 && find_brick_type(palette, code).is_none() {
+                return Err(LevelError::UnknownBrick { code: code, line: line_number, column: column });
+            }
+            row.push(code);
+        }
+
+        // blank lines (e.g. around a multi-line TOML string) are not rows
+        if row.is_empty() {
+            continue;
+        }
+        if let Some(first_row) = tile_data.first() {
+            if row.len() != first_row.len() {
+                return Err(LevelError::RaggedRow { line: line_number, expected: first_row.len(), found: row.len() });
+            }
+        }
+        tile_data.push(row);
+    }
+
+    // a level without a brick to clear would be completed before it even started
+    let clearable = tile_data.iter().flatten().filter_map(|code| find_brick_type(palette, *code))
+        .any(|brick_type| !brick_type.solid && !brick_type.passthrough_only);
+    if !clearable {
+        return Err(LevelError::Empty);
+    }
+    Ok(tile_data)
+}
+
+// checks the entries of a TOML level file that its format alone allows to be wrong
+fn validate_level_file(level_file: &LevelFile, contents: &str) -> Result<(), LevelError> {
+    let info = &level_file.info;
+    for (key, value) in [("ball_speed", info.ball_speed), ("par_time", info.par_time)] {
+        if let Some(value) = value {
+            if !value.is_finite() || value <= 0.0 {
+                return Err(LevelError::InvalidValue {
+                    key: key.to_string(),
+                    reason: "must be greater than 0",
+                    line: entry_line(contents, key, None),
+                });
             }
         }
     }
-}
\ No newline at end of file
+    if level_file.palette.iter().any(|brick_type| brick_type.code == 0) {
+        return Err(LevelError::InvalidValue {
+            key: "code".to_string(),
+            reason: "0 is an empty tile and can't be a brick",
+            line: entry_line(contents, "code", Some("0")),
+        });
+    }
+    Ok(())
+}
+
+// line of a TOML level file that sets `key` (to `value`, if given), 1 if there is none
+fn entry_line(contents: &str, key: &str, value: Option<&str>) -> usize {
+    let entry = format!("{}={}", key, value.unwrap_or(""));
+    contents.lines()
+        .position(|line| {
+            let compact: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+            if value.is_some() { compact == entry } else { compact.starts_with(&entry) }
+        })
+        .map_or(1, |index| index + 1)
+}
+
+// splits a line on whitespace, keeping the column each token starts at
+fn tokens(line: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in line.char_indices().chain(std::iter::once((line.len(), ' '))) {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(i),
+            (Some(begin), true) => {
+                tokens.push((line[..begin].chars().count() + 1, &line[begin..i]));
+                start = None;
+            },
+            _ => ()
+        }
+    }
+    tokens
+}
+
+// line of a TOML level file the rows of its multi-line `tiles` string start on, so tile
+// errors can be reported against the file (the newline right after the quotes is dropped)
+fn tiles_first_line(contents: &str) -> usize {
+    contents.lines()
+        .position(|line| {
+            let line = line.trim();
+            line.starts_with("tiles") && (line.ends_with("\"\"\"") || line.ends_with("'''"))
+        })
+        .map_or(1, |index| index + 2)
+}
+
+// first and one past the last tile of a row or column of `count` tiles of size `unit` covered
+// by the range from `min` to `max`
+fn tile_range(min: f32, max: f32, unit: f32, count: usize) -> (usize, usize) {
+    if unit <= 0.0 || max < 0.0 || min >= unit * count as f32 {
+        return (0, 0);
+    }
+    let first = (min.max(0.0) / unit) as usize;
+    let last = ((max / unit) as usize + 1).min(count);
+    (first, last)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::level_pack::LevelPack;
+
+    const LEVEL_DIRECTORY: &str = "resources/levels";
+
+    fn parse(text: &str) -> Result<Vec<Vec<u32>>, LevelError> {
+        parse_tiles(text, 1, &default_palette())
+    }
+
+    #[test]
+    fn level_without_bricks_to_clear_is_empty() {
+        assert!(matches!(parse(""), Err(LevelError::Empty)));
+        assert!(matches!(parse("0 0 0\n0 0 0"), Err(LevelError::Empty)));
+        assert!(matches!(parse("1 1"), Err(LevelError::Empty)));
+        assert_eq!(parse("1 2\n0 0").unwrap(), vec![vec![1, 2], vec![0, 0]]);
+    }
+
+    #[test]
+    fn errors_point_at_the_line_and_column() {
+        assert!(matches!(parse("2 2\n2 x"), Err(LevelError::BadToken { line: 2, column: 3, .. })));
+        assert!(matches!(parse("2 2\n2"), Err(LevelError::RaggedRow { line: 2, expected: 2, found: 1 })));
+        assert!(matches!(parse("2  7"), Err(LevelError::UnknownBrick { code: 7, line: 1, column: 4 })));
+    }
+
+    fn validate(contents: &str) -> Result<(), LevelError> {
+        validate_level_file(&toml::from_str(contents).unwrap(), contents)
+    }
+
+    #[test]
+    fn nonsensical_values_are_rejected_at_their_line() {
+        let tiles = "tiles = \"2\"\n";
+        assert!(validate(&format!("ball_speed = 400.0\npar_time = 30.0\n{}", tiles)).is_ok());
+        assert!(matches!(
+            validate(&format!("name = \"fast\"\nball_speed = 0.0\n{}", tiles)),
+            Err(LevelError::InvalidValue { line: 2, .. })
+        ));
+        assert!(matches!(
+            validate(&format!("par_time = -5.0\n{}", tiles)),
+            Err(LevelError::InvalidValue { line: 1, .. })
+        ));
+        let palette = "\n[[palette]]\ncode = 6\ncolor = [1.0, 1.0, 1.0]\n\n[[palette]]\ncode = 0\ncolor = [1.0, 1.0, 1.0]\n";
+        assert!(matches!(
+            validate(&format!("{}{}", tiles, palette)),
+            Err(LevelError::InvalidValue { line: 8, .. })
+        ));
+    }
+
+    // a level of `tiles` laid out on 800x300, where bricks of code 2 take three hits
+    fn level_with(tiles: Vec<Vec<u32>>) -> GameLevel {
+        let mut level = GameLevel::new();
+        level.level_width = 800;
+        level.level_height = 300;
+        level.palette.iter_mut().filter(|brick_type| brick_type.code == 2).for_each(|brick_type| brick_type.hit_points = 3);
+        level.set_tiles(tiles);
+        level
+    }
+
+    // every brick the grid finds anywhere on the level
+    fn bricks_on_grid(level: &GameLevel) -> Vec<usize> {
+        let mut found: Vec<usize> = level.bricks_in(vec2(0.0, 0.0), vec2(800.0, 300.0)).collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn restoring_a_snapshot_brings_back_damaged_bricks() {
+        let mut level = level_with(vec![vec![2, 2, 0], vec![0, 2, 1]]);
+        let snapshot = level.snapshot();
+        level.damage_brick(0, 3);
+        level.damage_brick(1, 1);
+        assert!(level.bricks[0].game_object.destroyed);
+        assert_eq!(level.bricks[1].hit_points, 2);
+        assert_eq!(bricks_on_grid(&level), vec![1, 2, 3]);
+
+        level.restore(&snapshot);
+        assert!(level.bricks.iter().all(|brick| !brick.game_object.destroyed));
+        assert_eq!(level.bricks[0].hit_points, 3);
+        assert_eq!(level.bricks[1].hit_points, 3);
+        assert_eq!(bricks_on_grid(&level), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn grid_query_finds_the_same_bricks_as_checking_every_brick() {
+        let tiles = (0..8).map(|y| (0..15).map(|x| [0, 1, 2][(x * 7 + y * 3) % 3]).collect()).collect();
+        let mut level = level_with(tiles);
+        for i in (0..level.bricks.len()).step_by(4) {
+            level.damage_brick(i, 3);
+        }
+
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        for _ in 0..500 {
+            let min = vec2(rng.gen_range(-100.0..900.0), rng.gen_range(-100.0..400.0));
+            let max = min + vec2(rng.gen_range(0.0..200.0), rng.gen_range(0.0..100.0));
+            let mut found: Vec<usize> = level.bricks_in(min, max).collect();
+            found.sort();
+            // bricks left whose tile overlaps the area
+            let expected: Vec<usize> = level.bricks.iter().enumerate()
+                .filter(|(_, brick)| {
+                    let object = &brick.game_object;
+                    !object.destroyed
+                        && object.position.x <= max.x && object.position.x + object.size.x > min.x
+                        && object.position.y <= max.y && object.position.y + object.size.y > min.y
+                })
+                .map(|(i, _)| i)
+                .collect();
+            assert_eq!(found, expected, "{:?} to {:?}", min, max);
+        }
+    }
+
+    #[test]
+    fn every_level_of_the_pack_loads() {
+        let pack = LevelPack::discover(LEVEL_DIRECTORY);
+        assert!(!pack.levels.is_empty());
+        for entry in pack.levels {
+            let path = format!("{}/{}", LEVEL_DIRECTORY, entry.file);
+            let mut level = GameLevel::new();
+            if let Err(error) = level.load(&path, 800, 300) {
+                panic!("{}: {}", path, error);
+            }
+            assert!(!level.is_completed(), "{} is completed from the start", path);
+        }
+    }
+}
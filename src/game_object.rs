@@ -1,8 +1,5 @@
 use cgmath::{ vec2, vec3, Vector2, Vector3 };
 
-use crate::lib::texture::Texture2D;
-use crate::lib::sprite_renderer::SpriteRenderer;
-
 #[derive(Copy, Clone, Debug)]
 pub struct GameObject {
     // object state
@@ -13,8 +10,8 @@ pub struct GameObject {
     pub rotation: f32,
     pub is_solid: bool,
     pub destroyed: bool,
-    // render state
-    pub sprite: Texture2D,
+    // tells moving objects apart from one step to the next, see `Simulation::new_object_id`
+    pub id: u32,
 }
 
 impl GameObject {
@@ -27,13 +24,13 @@ impl GameObject {
             rotation: 0.0,
             is_solid: false,
             destroyed: false,
-            sprite: Texture2D::new(),
+            id: 0,
         };
 
         game_object
     }
 
-    pub fn new(pos: Vector2<f32>, size: Vector2<f32>, velocity: Vector2<f32>, color: Vector3<f32>, sprite: Texture2D) -> Self {
+    pub fn new(pos: Vector2<f32>, size: Vector2<f32>, velocity: Vector2<f32>, color: Vector3<f32>) -> Self {
         let game_object = GameObject {
             position: pos,
             size: size,
@@ -42,13 +39,9 @@ impl GameObject {
             rotation: 0.0,
             is_solid: false,
             destroyed: false,
-            sprite: sprite
+            id: 0,
         };
 
         game_object
     }
-
-    pub fn draw(&self, renderer: &SpriteRenderer) {
-        renderer.draw_sprite(&self.sprite, self.position, self.size, self.rotation, self.color);
-    }
 }
\ No newline at end of file
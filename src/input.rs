@@ -0,0 +1,367 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use glfw::{Action, GamepadAxis, GamepadButton, GamepadState, JoystickId, Key};
+
+use crate::settings::read_key_values;
+
+// Something the player can do, whatever keys or gamepad buttons it is bound to
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum InputAction {
+    MoveLeft,
+    MoveRight,
+    Launch,
+    Pause,
+    Menu,
+}
+
+impl InputAction {
+    // every action, in the order the bindings file and the rebinding screen list them
+    pub const ALL: [InputAction; 5] = [
+        InputAction::MoveLeft,
+        InputAction::MoveRight,
+        InputAction::Launch,
+        InputAction::Pause,
+        InputAction::Menu,
+    ];
+
+    // how the bindings file refers to it
+    pub fn name(self) -> &'static str {
+        match self {
+            InputAction::MoveLeft => "move_left",
+            InputAction::MoveRight => "move_right",
+            InputAction::Launch => "launch",
+            InputAction::Pause => "pause",
+            InputAction::Menu => "menu",
+        }
+    }
+
+    // how the rebinding screen shows it
+    pub fn label(self) -> &'static str {
+        match self {
+            InputAction::MoveLeft => "Move left",
+            InputAction::MoveRight => "Move right",
+            InputAction::Launch => "Launch",
+            InputAction::Pause => "Pause",
+            InputAction::Menu => "Menu",
+        }
+    }
+
+    fn default_keys(self) -> Vec<Key> {
+        match self {
+            InputAction::MoveLeft => vec![Key::A, Key::Left],
+            InputAction::MoveRight => vec![Key::D, Key::Right],
+            InputAction::Launch => vec![Key::Space],
+            InputAction::Pause => vec![Key::P, Key::Escape],
+            InputAction::Menu => vec![Key::M],
+        }
+    }
+
+    fn default_buttons(self) -> Vec<GamepadButton> {
+        match self {
+            InputAction::MoveLeft => vec![GamepadButton::ButtonDpadLeft],
+            InputAction::MoveRight => vec![GamepadButton::ButtonDpadRight],
+            InputAction::Launch => vec![GamepadButton::ButtonA],
+            InputAction::Pause => vec![GamepadButton::ButtonStart],
+            InputAction::Menu => vec![GamepadButton::ButtonBack, GamepadButton::ButtonB],
+        }
+    }
+
+    fn index(self) -> usize {
+        InputAction::ALL.iter().position(|action| *action == self).unwrap()
+    }
+}
+
+// Keys that can be bound, with the names the bindings file and the rebinding screen use for them
+pub static KEY_NAMES: &[(Key, &str)] = &[
+    (Key::A, "A"), (Key::B, "B"), (Key::C, "C"), (Key::D, "D"), (Key::E, "E"), (Key::F, "F"),
+    (Key::G, "G"), (Key::H, "H"), (Key::I, "I"), (Key::J, "J"), (Key::K, "K"), (Key::L, "L"),
+    (Key::M, "M"), (Key::N, "N"), (Key::O, "O"), (Key::P, "P"), (Key::Q, "Q"), (Key::R, "R"),
+    (Key::S, "S"), (Key::T, "T"), (Key::U, "U"), (Key::V, "V"), (Key::W, "W"), (Key::X, "X"),
+    (Key::Y, "Y"), (Key::Z, "Z"),
+    (Key::Num0, "0"), (Key::Num1, "1"), (Key::Num2, "2"), (Key::Num3, "3"), (Key::Num4, "4"),
+    (Key::Num5, "5"), (Key::Num6, "6"), (Key::Num7, "7"), (Key::Num8, "8"), (Key::Num9, "9"),
+    (Key::Space, "SPACE"), (Key::Enter, "ENTER"), (Key::Escape, "ESCAPE"), (Key::Tab, "TAB"),
+    (Key::Backspace, "BACKSPACE"), (Key::Insert, "INSERT"), (Key::Delete, "DELETE"),
+    (Key::Left, "LEFT"), (Key::Right, "RIGHT"), (Key::Up, "UP"), (Key::Down, "DOWN"),
+    (Key::Home, "HOME"), (Key::End, "END"), (Key::PageUp, "PAGE_UP"), (Key::PageDown, "PAGE_DOWN"),
+    (Key::LeftShift, "LEFT_SHIFT"), (Key::RightShift, "RIGHT_SHIFT"),
+    (Key::LeftControl, "LEFT_CONTROL"), (Key::RightControl, "RIGHT_CONTROL"),
+    (Key::LeftAlt, "LEFT_ALT"), (Key::RightAlt, "RIGHT_ALT"),
+    (Key::Comma, "COMMA"), (Key::Period, "PERIOD"), (Key::Slash, "SLASH"), (Key::Semicolon, "SEMICOLON"),
+    (Key::Apostrophe, "APOSTROPHE"), (Key::Minus, "MINUS"), (Key::Equal, "EQUAL"),
+    (Key::LeftBracket, "LEFT_BRACKET"), (Key::RightBracket, "RIGHT_BRACKET"),
+    (Key::Backslash, "BACKSLASH"), (Key::GraveAccent, "GRAVE_ACCENT"), (Key::World1, "WORLD_1"),
+    (Key::Kp0, "KP_0"), (Key::Kp1, "KP_1"), (Key::Kp2, "KP_2"), (Key::Kp3, "KP_3"), (Key::Kp4, "KP_4"),
+    (Key::Kp5, "KP_5"), (Key::Kp6, "KP_6"), (Key::Kp7, "KP_7"), (Key::Kp8, "KP_8"), (Key::Kp9, "KP_9"),
+    (Key::KpEnter, "KP_ENTER"), (Key::KpAdd, "KP_ADD"), (Key::KpSubtract, "KP_SUBTRACT"),
+    (Key::KpMultiply, "KP_MULTIPLY"), (Key::KpDivide, "KP_DIVIDE"), (Key::KpDecimal, "KP_DECIMAL"),
+    (Key::F1, "F1"), (Key::F2, "F2"), (Key::F3, "F3"), (Key::F4, "F4"), (Key::F5, "F5"), (Key::F6, "F6"),
+    (Key::F7, "F7"), (Key::F8, "F8"), (Key::F9, "F9"), (Key::F10, "F10"), (Key::F11, "F11"), (Key::F12, "F12"),
+];
+
+// Gamepad buttons by the names the bindings file and the rebinding screen use for them,
+// laid out like an Xbox controller whatever the gamepad
+pub static BUTTON_NAMES: &[(GamepadButton, &str)] = &[
+    (GamepadButton::ButtonA, "PAD_A"), (GamepadButton::ButtonB, "PAD_B"),
+    (GamepadButton::ButtonX, "PAD_X"), (GamepadButton::ButtonY, "PAD_Y"),
+    (GamepadButton::ButtonLeftBumper, "PAD_LB"), (GamepadButton::ButtonRightBumper, "PAD_RB"),
+    (GamepadButton::ButtonBack, "PAD_BACK"), (GamepadButton::ButtonStart, "PAD_START"),
+    (GamepadButton::ButtonLeftThumb, "PAD_LS"), (GamepadButton::ButtonRightThumb, "PAD_RS"),
+    (GamepadButton::ButtonDpadUp, "PAD_UP"), (GamepadButton::ButtonDpadDown, "PAD_DOWN"),
+    (GamepadButton::ButtonDpadLeft, "PAD_LEFT"), (GamepadButton::ButtonDpadRight, "PAD_RIGHT"),
+];
+
+pub fn key_name(key: Key) -> &'static str {
+    KEY_NAMES.iter().find(|(named, _)| *named == key).map_or("?", |(_, name)| name)
+}
+
+// looks up a key by name, ignoring case
+pub fn find_key(name: &str) -> Option<Key> {
+    KEY_NAMES.iter().find(|(_, named)| named.eq_ignore_ascii_case(name)).map(|(key, _)| *key)
+}
+
+pub fn button_name(button: GamepadButton) -> &'static str {
+    BUTTON_NAMES.iter().find(|(named, _)| *named == button).map_or("?", |(_, name)| name)
+}
+
+pub fn find_button(name: &str) -> Option<GamepadButton> {
+    BUTTON_NAMES.iter().find(|(_, named)| named.eq_ignore_ascii_case(name)).map(|(button, _)| *button)
+}
+
+// The keys and gamepad buttons bound to every action, any number of them each
+#[derive(Clone, Debug)]
+pub struct Bindings {
+    // both in the order of InputAction::ALL
+    keys: Vec<Vec<Key>>,
+    buttons: Vec<Vec<GamepadButton>>,
+    // the bindings shipped with the game, and the player's own that override them
+    defaults_path: String,
+    file_path: String,
+    // whether they differ from what is saved in `file_path`
+    changed: bool,
+}
+
+impl Bindings {
+    // the default bindings from `defaults_path`, built in ones for any action it doesn't list,
+    // saved to `file_path` once changed
+    pub fn new(defaults_path: &str, file_path: &str) -> Self {
+        let mut bindings = Bindings {
+            keys: InputAction::ALL.iter().map(|action| action.default_keys()).collect(),
+            buttons: InputAction::ALL.iter().map(|action| action.default_buttons()).collect(),
+            defaults_path: defaults_path.to_string(),
+            file_path: file_path.to_string(),
+            changed: false,
+        };
+        if !bindings.read(defaults_path) {
+            println!("ERROR::INPUT: Failed to open {}, using the built in bindings", defaults_path);
+        }
+        bindings
+    }
+
+    // the default bindings with the ones the player saved on top, if any
+    pub fn load(defaults_path: &str, file_path: &str) -> Self {
+        let mut bindings = Bindings::new(defaults_path, file_path);
+        bindings.read(file_path);
+        bindings
+    }
+
+    // replaces the bindings of every action a bindings file lists, false if it can't be opened
+    fn read(&mut self, file_path: &str) -> bool {
+        let entries = match read_key_values(file_path) {
+            Some(entries) => entries,
+            None => return false,
+        };
+        for (line, name, value) in entries {
+            let action = match InputAction::ALL.iter().find(|action| action.name() == name) {
+                Some(action) => *action,
+                None => {
+                    println!("ERROR::INPUT: Unknown action `{}` on line {} of {}", name, line, file_path);
+                    continue;
+                }
+            };
+            // keys and buttons are listed together, an action with only keys listed has no buttons
+            let mut keys = Vec::new();
+            let mut buttons = Vec::new();
+            for name in value.split(',').map(|name| name.trim()).filter(|name| !name.is_empty()) {
+                if let Some(key) = find_key(name) {
+                    if !keys.contains(&key) {
+                        keys.push(key);
+                    }
+                } else if let Some(button) = find_button(name) {
+                    if !buttons.contains(&button) {
+                        buttons.push(button);
+                    }
+                } else {
+                    println!("ERROR::INPUT: Unknown key `{}` on line {} of {}", name, line, file_path);
+                }
+            }
+            self.keys[action.index()] = keys;
+            self.buttons[action.index()] = buttons;
+        }
+        true
+    }
+
+    // writes the bindings to the player's file, unless nothing changed since it was read or written
+    pub fn save(&mut self) {
+        if !self.changed {
+            return;
+        }
+        if let Some(directory) = Path::new(&self.file_path).parent() {
+            if fs::create_dir_all(directory).is_err() {
+                println!("ERROR::INPUT: Failed to create {}", directory.display());
+                return;
+            }
+        }
+
+        let mut contents = String::from("# keys and gamepad buttons bound to each action, separated by commas\n");
+        for action in InputAction::ALL.iter() {
+            contents += &format!("{} = {}\n", action.name(), self.names(*action).join(", "));
+        }
+        match fs::write(&self.file_path, contents) {
+            Ok(()) => self.changed = false,
+            Err(_) => println!("ERROR::INPUT: Failed to write {}", self.file_path),
+        }
+    }
+
+    pub fn keys(&self, action: InputAction) -> &[Key] {
+        &self.keys[action.index()]
+    }
+
+    pub fn buttons(&self, action: InputAction) -> &[GamepadButton] {
+        &self.buttons[action.index()]
+    }
+
+    // adds a key to an action, the key keeps any other action it is bound to
+    pub fn bind(&mut self, action: InputAction, key: Key) {
+        let keys = &mut self.keys[action.index()];
+        if !keys.contains(&key) {
+            keys.push(key);
+            self.changed = true;
+        }
+    }
+
+    pub fn bind_button(&mut self, action: InputAction, button: GamepadButton) {
+        let buttons = &mut self.buttons[action.index()];
+        if !buttons.contains(&button) {
+            buttons.push(button);
+            self.changed = true;
+        }
+    }
+
+    pub fn clear(&mut self, action: InputAction) {
+        self.changed |= !self.keys[action.index()].is_empty() || !self.buttons[action.index()].is_empty();
+        self.keys[action.index()].clear();
+        self.buttons[action.index()].clear();
+    }
+
+    // back to the default bindings, saving them replaces the player's own
+    pub fn reset(&mut self) {
+        let defaults = Bindings::new(&self.defaults_path, &self.file_path);
+        self.changed |= defaults.keys != self.keys || defaults.buttons != self.buttons;
+        self.keys = defaults.keys;
+        self.buttons = defaults.buttons;
+    }
+
+    // the keys of an action as shown to the player, like `P/ESCAPE`, followed by
+    // its gamepad buttons while one is connected
+    pub fn describe(&self, action: InputAction, gamepad: bool) -> String {
+        let mut names: Vec<&str> = self.keys(action).iter().map(|key| key_name(*key)).collect();
+        if gamepad {
+            names.extend(self.buttons(action).iter().map(|button| button_name(*button)));
+        }
+        if names.is_empty() {
+            "(unbound)".to_string()
+        } else {
+            names.join("/")
+        }
+    }
+
+    fn names(&self, action: InputAction) -> Vec<&'static str> {
+        let keys = self.keys(action).iter().map(|key| key_name(*key));
+        keys.chain(self.buttons(action).iter().map(|button| button_name(*button))).collect()
+    }
+}
+
+// The gamepad played with besides the keyboard, the first connected one GLFW knows the layout of
+pub struct Gamepad {
+    pub joystick: Option<JoystickId>,
+    pub name: String,
+    // buttons and sticks as of the last poll
+    state: Option<GamepadState>,
+    // buttons whose press has already been handled, until they are released
+    buttons_processed: HashSet<GamepadButton>,
+}
+
+impl Gamepad {
+    pub fn new() -> Self {
+        Gamepad {
+            joystick: None,
+            name: String::new(),
+            state: None,
+            buttons_processed: HashSet::new(),
+        }
+    }
+
+    // reads the gamepad, picking up one that was plugged in or letting go of one that
+    // was unplugged since the last poll, returns whether either happened
+    pub fn poll(&mut self, glfw: &glfw::Glfw) -> bool {
+        let mut changed = false;
+        if let Some(joystick) = self.joystick {
+            if !glfw.get_joystick(joystick).is_gamepad() {
+                self.joystick = None;
+                changed = true;
+            }
+        }
+        if self.joystick.is_none() {
+            let connected = (0..16).filter_map(JoystickId::from_i32).map(|id| glfw.get_joystick(id)).find(|joystick| joystick.is_gamepad());
+            if let Some(joystick) = connected {
+                self.name = joystick.get_gamepad_name().unwrap_or_else(|| "Gamepad".to_string());
+                self.joystick = Some(joystick.id);
+                changed = true;
+            }
+        }
+
+        self.state = self.joystick.and_then(|joystick| glfw.get_joystick(joystick).get_gamepad_state());
+        // forget buttons that have been released since the last poll
+        let state = self.state;
+        self.buttons_processed.retain(|button| state.is_some_and(|state| state.get_button_state(*button) == Action::Press));
+        changed
+    }
+
+    pub fn button_down(&self, button: GamepadButton) -> bool {
+        self.state.is_some_and(|state| state.get_button_state(button) == Action::Press)
+    }
+
+    // true only on the poll a button goes down
+    pub fn button_pressed(&mut self, button: GamepadButton) -> bool {
+        self.button_down(button) && self.buttons_processed.insert(button)
+    }
+
+    // true while a button is down, it then counts as handled until released
+    pub fn button_held(&mut self, button: GamepadButton) -> bool {
+        let down = self.button_down(button);
+        if down {
+            self.buttons_processed.insert(button);
+        }
+        down
+    }
+
+    // the first button going down on this poll, if any
+    pub fn any_button_pressed(&mut self) -> Option<GamepadButton> {
+        BUTTON_NAMES.iter().map(|(button, _)| *button).find(|button| self.button_pressed(*button))
+    }
+
+    // left stick from -1.0 (all the way left) to 1.0 (all the way right), 0.0 within the
+    // deadzone and growing from there, so the paddle can be steered at any speed
+    pub fn stick(&self, deadzone: f32) -> f32 {
+        let x = self.state.map_or(0.0, |state| state.get_axis(GamepadAxis::AxisLeftX));
+        if x.abs() <= deadzone {
+            return 0.0;
+        }
+        let steer = ((x.abs() - deadzone) / (1.0 - deadzone)).min(1.0);
+        // in hundredths, which keeps replays of analog play compact
+        (steer * 100.0).round() / 100.0 * x.signum()
+    }
+}
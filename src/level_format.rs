@@ -0,0 +1,113 @@
+use cgmath::{vec3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::power_up::POWER_UP_KINDS;
+
+// Metadata of a level, every entry is optional
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct LevelInfo {
+    pub name: Option<String>,
+    pub author: Option<String>,
+    // time in seconds to beat for a bonus
+    pub par_time: Option<f32>,
+    // texture drawn behind the bricks
+    pub background: Option<String>,
+    // music track of the level
+    pub music: Option<String>,
+    // initial speed of the ball in pixels per second
+    pub ball_speed: Option<f32>,
+}
+
+// Chance of a power-up dropping from a destroyed brick
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct PowerUpDrop {
+    pub power_up: String,
+    // spawns with a 1 in `chance` probability
+    pub chance: u32,
+}
+
+// Everything a brick code in the tile grid stands for
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct BrickType {
+    pub code: u32,
+    pub color: [f32; 3],
+    #[serde(default = "default_texture")]
+    pub texture: String,
+    #[serde(default)]
+    pub solid: bool,
+    #[serde(default = "default_hit_points")]
+    pub hit_points: u32,
+    // texture swapped in once the brick has taken a hit
+    #[serde(default)]
+    pub damaged_texture: Option<String>,
+    // only a passthrough ball can damage the brick, so clearing the level doesn't require it
+    #[serde(default)]
+    pub passthrough_only: bool,
+    #[serde(default)]
+    pub points: u32,
+    // tried in order until one of them spawns, defaults to the standard drops
+    #[serde(default = "default_drops")]
+    pub drops: Vec<PowerUpDrop>,
+}
+
+impl BrickType {
+    pub fn new(code: u32, color: Vector3<f32>, texture: &str, solid: bool, points: u32) -> Self {
+        BrickType {
+            code: code,
+            color: color.into(),
+            texture: texture.to_string(),
+            solid: solid,
+            hit_points: 1,
+            damaged_texture: None,
+            passthrough_only: false,
+            points: points,
+            drops: if solid { Vec::new() } else { default_drops() },
+        }
+    }
+
+    pub fn color(&self) -> Vector3<f32> {
+        Vector3::from(self.color)
+    }
+}
+
+// Layout of a level file in the data-driven (TOML) format
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LevelFile {
+    #[serde(flatten)]
+    pub info: LevelInfo,
+    // rows of whitespace-separated brick codes, like the legacy .lvl format
+    pub tiles: String,
+    // brick types added or overridden by this level
+    #[serde(default)]
+    pub palette: Vec<BrickType>,
+}
+
+// The brick codes understood by every level (and all that legacy .lvl files can use)
+pub fn default_palette() -> Vec<BrickType> {
+    vec![
+        BrickType::new(1, vec3(0.8, 0.8, 0.7), "block_solid", true, 0),
+        BrickType::new(2, vec3(0.2, 0.6, 1.0), "block", false, 10),
+        BrickType::new(3, vec3(0.0, 0.7, 0.0), "block", false, 20),
+        BrickType::new(4, vec3(0.8, 0.8, 0.4), "block", false, 30),
+        BrickType::new(5, vec3(1.0, 0.5, 0.0), "block", false, 50),
+    ]
+}
+
+// looks up what a brick code stands for
+pub fn find_brick_type(palette: &[BrickType], code: u32) -> Option<&BrickType> {
+    palette.iter().find(|brick_type| brick_type.code == code)
+}
+
+fn default_texture() -> String {
+    "block".to_string()
+}
+
+fn default_hit_points() -> u32 {
+    1
+}
+
+fn default_drops() -> Vec<PowerUpDrop> {
+    POWER_UP_KINDS.iter()
+        .map(|kind| PowerUpDrop { power_up: kind.name.to_string(), chance: kind.chance })
+        .collect()
+}
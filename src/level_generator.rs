@@ -0,0 +1,297 @@
+use std::collections::VecDeque;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+// Brick codes of the default palette the generator builds with
+const EMPTY: u32 = 0;
+const SOLID: u32 = 1;
+const LOWEST_TIER: u32 = 2;
+const HIGHEST_TIER: u32 = 5;
+
+// Shape the bricks are laid out in
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Pattern {
+    Random,
+    Pyramid,
+    Checkerboard,
+    Maze,
+}
+
+impl Pattern {
+    pub const ALL: [Pattern; 4] = [Pattern::Random, Pattern::Pyramid, Pattern::Checkerboard, Pattern::Maze];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pattern::Random => "random",
+            Pattern::Pyramid => "pyramid",
+            Pattern::Checkerboard => "checkerboard",
+            Pattern::Maze => "maze",
+        }
+    }
+}
+
+// Everything a generated layout depends on, the same parameters always give the same tiles
+#[derive(Copy, Clone, Debug)]
+pub struct GeneratorParams {
+    pub seed: u64,
+    pub rows: usize,
+    pub columns: usize,
+    // part of the bricks that are solid, from 0.0 to 1.0
+    pub solid_density: f32,
+    // mirror the left half of the layout onto the right half
+    pub symmetric: bool,
+    // from 0.0 to 1.0, fills more of the grid with bricks of higher tiers
+    pub difficulty: f32,
+    pub pattern: Pattern,
+}
+
+impl GeneratorParams {
+    pub fn new(seed: u64, pattern: Pattern) -> Self {
+        GeneratorParams {
+            seed: seed,
+            rows: 8,
+            columns: 15,
+            solid_density: 0.1,
+            symmetric: true,
+            difficulty: 0.5,
+            pattern: pattern,
+        }
+    }
+}
+
+// builds a tile grid for `GameLevel` in which every breakable brick can be reached by the ball
+pub fn generate(params: &GeneratorParams) -> Vec<Vec<u32>> {
+    let mut rng = ChaCha8Rng::seed_from_u64(params.seed);
+    let rows = params.rows.max(1);
+    let columns = params.columns.max(1);
+    let difficulty = params.difficulty.clamp(0.0, 1.0);
+    let solid_density = params.solid_density.clamp(0.0, 1.0);
+
+    // where the pattern puts bricks
+    let mut filled = vec![vec![false; columns]; rows];
+    match params.pattern {
+        Pattern::Random => {
+            let fill = 0.5 + 0.4 * difficulty;
+            for row in filled.iter_mut() {
+                for tile in row.iter_mut() {
+                    *tile = rng.gen_bool(fill as f64);
+                }
+            }
+        },
+        Pattern::Pyramid => {
+            // widest at the bottom, a random height so not every pyramid reaches the top
+            let top = rng.gen_range(0..rows.div_ceil(2));
+            for (y, row) in filled.iter_mut().enumerate().skip(top) {
+                let half_width = (y - top + 1) as f32 * columns as f32 / (2.0 * (rows - top) as f32);
+                let center = (columns as f32 - 1.0) / 2.0;
+                for (x, tile) in row.iter_mut().enumerate() {
+                    *tile = (x as f32 - center).abs() < half_width;
+                }
+            }
+        },
+        Pattern::Checkerboard => {
+            // squares of one or two tiles
+            let size = rng.gen_range(1..=2);
+            for (y, row) in filled.iter_mut().enumerate() {
+                for (x, tile) in row.iter_mut().enumerate() {
+                    *tile = (x / size + y / size) % 2 == 0;
+                }
+            }
+        },
+        Pattern::Maze => carve_maze(&mut filled, &mut rng),
+    }
+
+    // turn the shape into bricks: solid ones by density, higher tiers towards the top
+    let mut tiles = vec![vec![EMPTY; columns]; rows];
+    for y in 0..rows {
+        for x in 0..columns {
+            if !filled[y][x] {
+                continue;
+            }
+            tiles[y][x] = if rng.gen_bool(solid_density as f64) {
+                SOLID
+            } else {
+                let height = 1.0 - y as f32 / rows as f32;
+                let tier = height * (1.0 + difficulty) + rng.gen_range(-0.25..0.25);
+                let span = (HIGHEST_TIER - LOWEST_TIER) as f32;
+                LOWEST_TIER + (tier * span).round().max(0.0).min(span) as u32
+            };
+        }
+    }
+
+    if params.symmetric {
+        mirror(&mut tiles);
+    }
+    ensure_clearable(&mut tiles, params.symmetric);
+    tiles
+}
+
+// lays out a maze where the walls are bricks and the corridors are left open
+fn carve_maze(filled: &mut [Vec<bool>], rng: &mut ChaCha8Rng) {
+    let rows = filled.len();
+    let columns = filled[0].len();
+    for row in filled.iter_mut() {
+        for tile in row.iter_mut() {
+            *tile = true;
+        }
+    }
+
+    // depth-first search over the cells on even coordinates, opening the wall between neighbours
+    let mut stack = vec![(0usize, 0usize)];
+    filled[0][0] = false;
+    while let Some(&(x, y)) = stack.last() {
+        let mut neighbours = Vec::new();
+        if x >= 2 && filled[y][x - 2] { neighbours.push((x - 2, y)); }
+        if x + 2 < columns && filled[y][x + 2] { neighbours.push((x + 2, y)); }
+        if y >= 2 && filled[y - 2][x] { neighbours.push((x, y - 2)); }
+        if y + 2 < rows && filled[y + 2][x] { neighbours.push((x, y + 2)); }
+
+        if neighbours.is_empty() {
+            stack.pop();
+        } else {
+            let (next_x, next_y) = neighbours[rng.gen_range(0..neighbours.len())];
+            filled[(y + next_y) / 2][(x + next_x) / 2] = false;
+            filled[next_y][next_x] = false;
+            stack.push((next_x, next_y));
+        }
+    }
+}
+
+// copies the left half onto the right half
+fn mirror(tiles: &mut [Vec<u32>]) {
+    for row in tiles.iter_mut() {
+        let columns = row.len();
+        for x in 0..columns / 2 {
+            row[columns - 1 - x] = row[x];
+        }
+    }
+}
+
+// the ball enters the grid from below and can only get to a brick through empty tiles and
+// bricks it has already broken, so solid bricks walling in breakable ones are made breakable
+fn ensure_clearable(tiles: &mut [Vec<u32>], symmetric: bool) {
+    let rows = tiles.len();
+    let columns = tiles[0].len();
+
+    // a layout of nothing but empty and solid tiles can't be won
+    if !tiles.iter().flatten().any(|tile| *tile != EMPTY && *tile != SOLID) {
+        tiles[rows - 1][columns / 2] = LOWEST_TIER;
+    }
+
+    // 0-1 breadth-first search from below the grid, walking through a solid brick costs one
+    let mut cost = vec![vec![usize::MAX; columns]; rows];
+    let mut parent: Vec<Vec<Option<(usize, usize)>>> = vec![vec![None; columns]; rows];
+    let mut queue = VecDeque::new();
+    for x in 0..columns {
+        let start = (tiles[rows - 1][x] == SOLID) as usize;
+        cost[rows - 1][x] = start;
+        if start == 0 { queue.push_front((x, rows - 1)); } else { queue.push_back((x, rows - 1)); }
+    }
+    while let Some((x, y)) = queue.pop_front() {
+        let mut neighbours = Vec::new();
+        if x > 0 { neighbours.push((x - 1, y)); }
+        if x + 1 < columns { neighbours.push((x + 1, y)); }
+        if y > 0 { neighbours.push((x, y - 1)); }
+        if y + 1 < rows { neighbours.push((x, y + 1)); }
+        for (next_x, next_y) in neighbours {
+            let step = (tiles[next_y][next_x] == SOLID) as usize;
+            if cost[y][x] + step < cost[next_y][next_x] {
+                cost[next_y][next_x] = cost[y][x] + step;
+                parent[next_y][next_x] = Some((x, y));
+                if step == 0 { queue.push_front((next_x, next_y)); } else { queue.push_back((next_x, next_y)); }
+            }
+        }
+    }
+
+    // open up the cheapest way to every breakable brick that is walled in
+    for y in 0..rows {
+        for x in 0..columns {
+            if tiles[y][x] == EMPTY || tiles[y][x] == SOLID || cost[y][x] == 0 {
+                continue;
+            }
+            let mut tile = Some((x, y));
+            while let Some((path_x, path_y)) = tile {
+                if tiles[path_y][path_x] == SOLID {
+                    tiles[path_y][path_x] = LOWEST_TIER;
+                    // keep the layout symmetric, opening more never walls anything in
+                    if symmetric && tiles[path_y][columns - 1 - path_x] == SOLID {
+                        tiles[path_y][columns - 1 - path_x] = LOWEST_TIER;
+                    }
+                }
+                tile = parent[path_y][path_x];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every combination the tests run the generator with, dense solids make walls likely
+    fn all_params() -> Vec<GeneratorParams> {
+        let mut all = Vec::new();
+        for pattern in Pattern::ALL {
+            for seed in 0..25 {
+                for (rows, columns) in [(8, 15), (1, 1), (3, 20), (12, 6)] {
+                    for symmetric in [true, false] {
+                        let mut params = GeneratorParams::new(seed, pattern);
+                        params.rows = rows;
+                        params.columns = columns;
+                        params.symmetric = symmetric;
+                        params.solid_density = (seed % 5) as f32 * 0.2;
+                        params.difficulty = (seed % 3) as f32 * 0.5;
+                        all.push(params);
+                    }
+                }
+            }
+        }
+        all
+    }
+
+    // tiles the ball can get to from below the grid without breaking through a solid brick
+    fn reachable(tiles: &[Vec<u32>]) -> Vec<Vec<bool>> {
+        let rows = tiles.len();
+        let columns = tiles[0].len();
+        let mut reached = vec![vec![false; columns]; rows];
+        let mut stack: Vec<(usize, usize)> = (0..columns).map(|x| (x, rows - 1)).collect();
+        while let Some((x, y)) = stack.pop() {
+            if reached[y][x] || tiles[y][x] == SOLID {
+                continue;
+            }
+            reached[y][x] = true;
+            if x > 0 { stack.push((x - 1, y)); }
+            if x + 1 < columns { stack.push((x + 1, y)); }
+            if y > 0 { stack.push((x, y - 1)); }
+            if y + 1 < rows { stack.push((x, y + 1)); }
+        }
+        reached
+    }
+
+    #[test]
+    fn same_params_give_the_same_tiles() {
+        for params in all_params() {
+            assert_eq!(generate(&params), generate(&params), "{:?}", params);
+        }
+    }
+
+    #[test]
+    fn every_breakable_brick_can_be_reached() {
+        for params in all_params() {
+            let tiles = generate(&params);
+            assert_eq!(tiles.len(), params.rows);
+            assert!(tiles.iter().all(|row| row.len() == params.columns));
+            assert!(tiles.iter().flatten().any(|tile| (LOWEST_TIER..=HIGHEST_TIER).contains(tile)), "{:?}", params);
+
+            let reached = reachable(&tiles);
+            for (y, row) in tiles.iter().enumerate() {
+                for (x, tile) in row.iter().enumerate() {
+                    if *tile != EMPTY && *tile != SOLID {
+                        assert!(reached[y][x], "brick at {}, {} is walled in for {:?}", x, y, params);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+
+// File in a level directory that lists its levels
+pub const MANIFEST_FILE: &str = "pack.toml";
+
+// When a level of the pack can be picked from the level select
+#[derive(Copy, Clone, PartialEq, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Unlock {
+    // once the campaign has reached it
+    #[default]
+    Progress,
+    // from the start
+    Always,
+}
+
+// A level of the pack as listed in its manifest
+#[derive(Clone, Debug, Deserialize)]
+pub struct PackEntry {
+    // path relative to the level directory
+    pub file: String,
+    // overrides the name given in the level file
+    pub name: Option<String>,
+    #[serde(default)]
+    pub unlock: Unlock,
+}
+
+impl PackEntry {
+    pub fn new(file: &str) -> Self {
+        PackEntry {
+            file: file.to_string(),
+            name: None,
+            unlock: Unlock::default(),
+        }
+    }
+}
+
+// Layout of a level pack manifest
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct LevelPack {
+    pub name: Option<String>,
+    #[serde(default)]
+    pub levels: Vec<PackEntry>,
+}
+
+impl LevelPack {
+    // reads the manifest of a level directory (if any) and adds every level file it doesn't
+    // list, so dropping a new file into the directory adds it to the end of the campaign
+    pub fn discover(directory: &str) -> Self {
+        let manifest_path = Path::new(directory).join(MANIFEST_FILE);
+        let mut pack = match fs::read_to_string(&manifest_path) {
+            Ok(contents) => match toml::from_str::<LevelPack>(&contents) {
+                Ok(pack) => pack,
+                Err(error) => {
+                    println!("ERROR::LEVEL: Failed to parse {}: {}", manifest_path.display(), error);
+                    LevelPack::default()
+                }
+            },
+            Err(_) => LevelPack::default(),
+        };
+
+        let mut unlisted: Vec<String> = Vec::new();
+        match fs::read_dir(directory) {
+            Ok(entries) => {
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    let is_level = path.extension().is_some_and(|ext| ext == "lvl" || ext == "toml");
+                    let file = entry.file_name().to_string_lossy().to_string();
+                    if is_level && file != MANIFEST_FILE && !pack.levels.iter().any(|level| level.file == file) {
+                        unlisted.push(file);
+                    }
+                }
+            },
+            Err(_) => println!("ERROR::LEVEL: Failed to read level directory {}", directory),
+        }
+        unlisted.sort();
+        pack.levels.extend(unlisted.iter().map(|file| PackEntry::new(file)));
+
+        pack
+    }
+
+    // points the manifest entry of a level at another file, e.g. after the level was converted
+    // to another format; only the `file = ...` line is rewritten so the manifest keeps its comments
+    pub fn rename_in_manifest(directory: &str, old_file: &str, new_file: &str) -> io::Result<()> {
+        let manifest_path = Path::new(directory).join(MANIFEST_FILE);
+        let contents = match fs::read_to_string(&manifest_path) {
+            Ok(contents) => contents,
+            // without a manifest the level is picked up under its new name anyway
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(error) => return Err(error),
+        };
+        match rename_file_entry(&contents, old_file, new_file) {
+            Some(contents) => fs::write(&manifest_path, contents),
+            None => Ok(()),
+        }
+    }
+}
+
+// the manifest with the `file` line naming old_file changed to new_file, None if no entry names it
+fn rename_file_entry(contents: &str, old_file: &str, new_file: &str) -> Option<String> {
+    let old_line = format!("file=\"{}\"", old_file);
+    let mut renamed = false;
+    let lines: Vec<String> = contents.lines().map(|line| {
+        let compact: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+        if compact == old_line {
+            renamed = true;
+            let indent = &line[..line.len() - line.trim_start().len()];
+            format!("{}file = \"{}\"", indent, new_file)
+        } else {
+            line.to_string()
+        }
+    }).collect();
+    if !renamed {
+        return None;
+    }
+    let mut renamed_contents = lines.join("\n");
+    if contents.ends_with('\n') {
+        renamed_contents.push('\n');
+    }
+    Some(renamed_contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renaming_an_entry_keeps_the_rest_of_the_manifest() {
+        let manifest = "# campaign order\nname = \"Breakout\"\n\n[[levels]]\nfile = \"one.lvl\"\nname = \"Standard\"\n\n[[levels]]\nfile = \"two.lvl\"\n";
+        let renamed = rename_file_entry(manifest, "one.lvl", "one.toml").unwrap();
+        assert_eq!(renamed, manifest.replace("one.lvl", "one.toml"));
+        let pack: LevelPack = toml::from_str(&renamed).unwrap();
+        assert_eq!(pack.levels[0].file, "one.toml");
+        assert_eq!(pack.levels[0].name.as_deref(), Some("Standard"));
+        assert!(rename_file_entry(manifest, "three.lvl", "three.toml").is_none());
+    }
+}
@@ -20,6 +20,7 @@ pub struct PostProcessor {
     pub confuse: bool,
     pub shake: bool,
     pub chaos: bool,
+    pub brightness: f32,
     // render state
     msfbo: u32, // MSFBO = Multisampled FBO
     fbo: u32, // FBO is regular, used for blitting MS color-buffer to texture
@@ -37,6 +38,7 @@ impl PostProcessor {
             confuse: false,
             shake: false,
             chaos: false,
+            brightness: 1.0,
             msfbo: 0,
             fbo: 0,
             rbo: 0,
@@ -55,6 +57,7 @@ impl PostProcessor {
             confuse: false,
             shake: false,
             chaos: false,
+            brightness: 1.0,
             msfbo: 0,
             fbo: 0,
             rbo: 0,
@@ -141,6 +144,7 @@ impl PostProcessor {
         self.post_processing_shader.upload_uniform_int("confuse", self.confuse as i32);
         self.post_processing_shader.upload_uniform_int("chaos", self.chaos as i32);
         self.post_processing_shader.upload_uniform_int("shake", self.shake as i32);
+        self.post_processing_shader.upload_uniform_float("brightness", self.brightness);
 
         // render textured quad
         gl::ActiveTexture(gl::TEXTURE0);
@@ -0,0 +1,261 @@
+use std::ffi::CStr;
+use std::fs;
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+
+use ab_glyph::{Font, FontVec, PxScale, ScaleFont};
+use gl;
+use self::gl::types::*;
+use cgmath::{vec2, Vector2, Vector3};
+
+use crate::lib::shader::Shader;
+use crate::lib::texture::Texture2D;
+
+// Range of characters rasterized into the glyph atlas (printable ASCII)
+const FIRST_CHAR: u8 = 32;
+const LAST_CHAR: u8 = 126;
+// Width of the glyph atlas in pixels, the height grows with the font size
+const ATLAS_WIDTH: u32 = 512;
+// Empty pixels kept around every glyph so filtering doesn't bleed into neighbours
+const GLYPH_PADDING: u32 = 2;
+
+// Horizontal alignment of a string relative to the given position
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+}
+
+// Holds all state information relevant to a character as loaded from the font
+#[derive(Copy, Clone, Debug)]
+struct Character {
+    // texture coordinates of the glyph within the atlas
+    uv_min: Vector2<f32>,
+    uv_max: Vector2<f32>,
+    // size of the glyph in pixels
+    size: Vector2<f32>,
+    // offset from the pen position on the baseline to the top-left of the glyph
+    bearing: Vector2<f32>,
+    // horizontal offset to advance to the next glyph
+    advance: f32,
+}
+
+// Renders text from a glyph atlas rasterized from a TTF/OTF font
+pub struct TextRenderer {
+    characters: Vec<Character>,
+    // distance from the top of a line to its baseline, in pixels at scale 1.0
+    ascent: f32,
+    // height of a line of text in pixels at scale 1.0
+    pub line_height: f32,
+    pub shader: Shader,
+    pub texture: Texture2D,
+    vao: u32,
+    vbo: u32,
+}
+
+impl TextRenderer {
+    pub const fn new_empty() -> Self {
+        TextRenderer {
+            characters: Vec::new(),
+            ascent: 0.0,
+            line_height: 0.0,
+            shader: Shader { id: 0 },
+            texture: Texture2D::new_empty(),
+            vao: 0,
+            vbo: 0,
+        }
+    }
+
+    pub fn new(shader: Shader) -> Self {
+        let mut text_renderer = TextRenderer::new_empty();
+        text_renderer.shader = shader;
+        text_renderer.init_render_data();
+
+        text_renderer
+    }
+
+    // rasterizes the printable ASCII characters of a font into the glyph atlas
+    pub fn load(&mut self, font_path: &str, font_size: u32) {
+        let font_data = fs::read(font_path)
+            .unwrap_or_else(|_| panic!("Failed to open {}", font_path));
+        let font = FontVec::try_from_vec(font_data)
+            .unwrap_or_else(|_| panic!("Failed to parse font {}", font_path));
+        let scaled_font = font.as_scaled(PxScale::from(font_size as f32));
+
+        self.ascent = scaled_font.ascent();
+        self.line_height = scaled_font.height() + scaled_font.line_gap();
+
+        // first pass: rasterize every glyph and pack them in rows
+        let mut glyphs: Vec<(Vec<u8>, u32, u32)> = Vec::new();
+        let mut placements: Vec<(u32, u32)> = Vec::new();
+        let (mut pen_x, mut pen_y, mut row_height) = (GLYPH_PADDING, GLYPH_PADDING, 0);
+        self.characters.clear();
+
+        for code in FIRST_CHAR..=LAST_CHAR {
+            let glyph_id = font.glyph_id(code as char);
+            let glyph = glyph_id.with_scale(scaled_font.scale());
+
+            let mut character = Character {
+                uv_min: vec2(0.0, 0.0),
+                uv_max: vec2(0.0, 0.0),
+                size: vec2(0.0, 0.0),
+                bearing: vec2(0.0, 0.0),
+                advance: scaled_font.h_advance(glyph_id),
+            };
+
+            let (bitmap, width, height) = match font.outline_glyph(glyph) {
+                Some(outline) => {
+                    let bounds = outline.px_bounds();
+                    let width = bounds.width() as u32;
+                    let height = bounds.height() as u32;
+                    let mut bitmap = vec![0u8; (width * height) as usize];
+                    outline.draw(|x, y, coverage| {
+                        if x < width && y < height {
+                            bitmap[(y * width + x) as usize] = (coverage * 255.0) as u8;
+                        }
+                    });
+                    character.size = vec2(width as f32, height as f32);
+                    character.bearing = vec2(bounds.min.x, bounds.min.y);
+                    (bitmap, width, height)
+                },
+                // whitespace has nothing to draw, only an advance
+                None => (Vec::new(), 0, 0),
+            };
+
+            if pen_x + width + GLYPH_PADDING > ATLAS_WIDTH {
+                pen_x = GLYPH_PADDING;
+                pen_y += row_height + GLYPH_PADDING;
+                row_height = 0;
+            }
+            placements.push((pen_x, pen_y));
+            pen_x += width + GLYPH_PADDING;
+            row_height = row_height.max(height);
+
+            glyphs.push((bitmap, width, height));
+            self.characters.push(character);
+        }
+        let atlas_height = (pen_y + row_height + GLYPH_PADDING).next_power_of_two();
+
+        // second pass: copy the glyphs into the atlas and compute their texture coordinates
+        let mut atlas = vec![0u8; (ATLAS_WIDTH * atlas_height) as usize];
+        for (i, (bitmap, width, height)) in glyphs.iter().enumerate() {
+            let (x, y) = placements[i];
+            for row in 0..*height {
+                let src = (row * width) as usize;
+                let dst = ((y + row) * ATLAS_WIDTH + x) as usize;
+                atlas[dst..dst + *width as usize].copy_from_slice(&bitmap[src..src + *width as usize]);
+            }
+            self.characters[i].uv_min = vec2(x as f32 / ATLAS_WIDTH as f32, y as f32 / atlas_height as f32);
+            self.characters[i].uv_max = vec2(
+                (x + width) as f32 / ATLAS_WIDTH as f32,
+                (y + height) as f32 / atlas_height as f32
+            );
+        }
+
+        // upload the atlas as a single channel texture
+        self.texture = Texture2D::new();
+        self.texture.internal_format = gl::RED;
+        self.texture.image_format = gl::RED;
+        self.texture.wrap_s = gl::CLAMP_TO_EDGE;
+        self.texture.wrap_t = gl::CLAMP_TO_EDGE;
+        unsafe {
+            // glyph rows are tightly packed bytes
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            self.texture.generate(ATLAS_WIDTH, atlas_height, atlas);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+        }
+    }
+
+    // width in pixels the text takes up when rendered at the given scale
+    pub fn measure(&self, text: &str, scale: f32) -> f32 {
+        text.chars().map(|c| self.character(c).advance * scale).sum()
+    }
+
+    // renders a string with its top at `position.y`, aligned horizontally around `position.x`
+    pub fn render_text(&self, text: &str, position: Vector2<f32>, scale: f32, color: Vector3<f32>, align: TextAlign) {
+        let mut x = match align {
+            TextAlign::Left => position.x,
+            TextAlign::Center => position.x - self.measure(text, scale) / 2.0,
+            TextAlign::Right => position.x - self.measure(text, scale),
+        };
+        let baseline = position.y + self.ascent * scale;
+
+        unsafe {
+            self.shader.use_program();
+            let name = CStr::from_bytes_with_nul_unchecked(concat!("textColor", "\0").as_bytes());
+            self.shader.set_vector3(name, &color);
+            gl::ActiveTexture(gl::TEXTURE0);
+            self.texture.bind();
+            gl::BindVertexArray(self.vao);
+
+            for c in text.chars() {
+                let ch = self.character(c);
+
+                let x_pos = x + ch.bearing.x * scale;
+                let y_pos = baseline + ch.bearing.y * scale;
+                let w = ch.size.x * scale;
+                let h = ch.size.y * scale;
+
+                if w > 0.0 && h > 0.0 {
+                    // update VBO for each character
+                    let vertices: [f32; 24] = [
+                        x_pos,     y_pos + h, ch.uv_min.x, ch.uv_max.y,
+                        x_pos + w, y_pos,     ch.uv_max.x, ch.uv_min.y,
+                        x_pos,     y_pos,     ch.uv_min.x, ch.uv_min.y,
+
+                        x_pos,     y_pos + h, ch.uv_min.x, ch.uv_max.y,
+                        x_pos + w, y_pos + h, ch.uv_max.x, ch.uv_max.y,
+                        x_pos + w, y_pos,     ch.uv_max.x, ch.uv_min.y
+                    ];
+                    gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+                    gl::BufferSubData(gl::ARRAY_BUFFER,
+                                      0,
+                                      (vertices.len() * mem::size_of::<GLfloat>()) as GLsizeiptr,
+                                      &vertices[0] as *const f32 as *const c_void);
+                    gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+                    gl::DrawArrays(gl::TRIANGLES, 0, 6);
+                }
+
+                // now advance cursors for next glyph
+                x += ch.advance * scale;
+            }
+
+            gl::BindVertexArray(0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    // characters outside the atlas are drawn as '?'
+    fn character(&self, c: char) -> &Character {
+        let code = c as u32;
+        if code >= FIRST_CHAR as u32 && code <= LAST_CHAR as u32 {
+            &self.characters[(code - FIRST_CHAR as u32) as usize]
+        } else {
+            &self.characters[(b'?' - FIRST_CHAR) as usize]
+        }
+    }
+
+    // configures the VAO/VBO for texture quads, updated for every character
+    fn init_render_data(&mut self) {
+        unsafe {
+            gl::GenVertexArrays(1, &mut self.vao);
+            gl::GenBuffers(1, &mut self.vbo);
+
+            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferData(gl::ARRAY_BUFFER,
+                           (6 * 4 * mem::size_of::<GLfloat>()) as GLsizeiptr,
+                           ptr::null(),
+                           gl::DYNAMIC_DRAW);
+
+            let stride = 4 * mem::size_of::<GLfloat>() as GLsizei;
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 4, gl::FLOAT, gl::FALSE, stride, ptr::null());
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindVertexArray(0);
+        }
+    }
+}
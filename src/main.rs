@@ -4,18 +4,22 @@ use self::glfw::{Context};
 #[macro_use]
 extern crate lazy_static;
 
-mod ball;
+use breakout::{game_level, game_object, level_format, level_generator, level_pack};
+use breakout::{power_up, progress, replay, settings, simulation, timestep};
+use replay::Replay;
+use settings::Settings;
+
+mod editor;
 mod game;
 use game::Game;
-mod game_level;
-mod game_object;
+mod input;
 mod particle;
-mod power_up;
 mod resource_manager;
 mod lib {
     pub mod post_processor;
     pub mod shader;
     pub mod sprite_renderer;
+    pub mod text_renderer;
     pub mod texture;
     pub mod window;
 }
@@ -30,13 +34,37 @@ fn main() {
     let mut delta_time: f32; // time between current frame and last frame
     let mut last_frame: f32 = 0.0;
 
+    // checks that a replay plays out to the score it claims, run as `--verify-replay <file>`
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(index) = args.iter().position(|arg| arg == "--verify-replay") {
+        let verified = match args.get(index + 1).and_then(|file_path| Replay::load(file_path)) {
+            Some(replay) => match replay.verify() {
+                Ok(score) if score == replay.score => {
+                    println!("replay verified: score {}", score);
+                    true
+                },
+                Ok(score) => {
+                    println!("ERROR::REPLAY: Replay claims a score of {} but plays out to {}", replay.score, score);
+                    false
+                },
+                Err(error) => {
+                    println!("ERROR::REPLAY: {}", error);
+                    false
+                },
+            },
+            None => false,
+        };
+        std::process::exit(if verified { 0 } else { 1 });
+    }
+
     // Window
     // ------
     let (mut glfw, mut window, _events) = Window::create(SCR_WIDTH, SCR_HEIGHT, "BreakOut");
 
     // Game initialization
     // -------------------
-    let mut breakout = Game::new(SCR_WIDTH, SCR_HEIGHT);
+    let settings = Settings::load("resources/settings.cfg");
+    let mut breakout = Game::new(SCR_WIDTH, SCR_HEIGHT, &settings);
     unsafe {
         breakout.init();
     }
@@ -54,10 +82,10 @@ fn main() {
 
         // input
         // -----
-        breakout.process_input(&window, delta_time);
+        breakout.process_input(&window);
 
-        // update game state
-        // -----------------
+        // update game state (in fixed steps, see Settings::update_rate)
+        // -----------------------------------------------------------
         breakout.update(delta_time);
 
         // render
@@ -7,6 +7,7 @@ use cgmath::{vec2, vec4, Vector2, Vector4};
 use gl;
 use self::gl::types::*;
 use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
 
 use crate::game_object::GameObject;
 use crate::lib::shader::Shader;
@@ -91,15 +92,18 @@ impl ParticleGenerator {
         gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
     }
 
-    pub fn update(&mut self, dt: f32, object: &GameObject, new_particles: u32, offset: Vector2<f32>) {
-        // add new particles 
+    // adds new particles trailing an object
+    pub fn spawn(&mut self, object: &GameObject, new_particles: u32, offset: Vector2<f32>, rng: &mut ChaCha8Rng) {
         for _i in 0..new_particles {
             let unused_particle = unsafe { 
                 self.first_unused_particle() 
             };
 
-            self.respawn_particle(unused_particle as usize, object, offset);
+            self.respawn_particle(unused_particle as usize, object, offset, rng);
         }
+    }
+
+    pub fn update(&mut self, dt: f32) {
         // update all particles
         for i in 0..self.amount {
             let i = i as usize;
@@ -178,8 +182,7 @@ impl ParticleGenerator {
         return 0    
     }
 
-    fn respawn_particle(&mut self, index: usize, object: &GameObject, offset: Vector2<f32>) {
-        let mut rng = rand::thread_rng();
+    fn respawn_particle(&mut self, index: usize, object: &GameObject, offset: Vector2<f32>, rng: &mut ChaCha8Rng) {
         let random: f32 = ((rng.gen::<f32>() % 100.0) - 50.0) / 10.0;
         let r_color: f32 = 0.5 + ((rng.gen::<f32>() % 100.0) / 100.0);
         self.particles[index].position = vec2(object.position.x + random + offset.x, object.position.y + random + offset.y);
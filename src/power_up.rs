@@ -1,33 +1,342 @@
-use cgmath::{vec2, Vector2, Vector3};
+use cgmath::{vec2, vec3, Basis2, Rad, Vector2, Vector3};
+use cgmath::prelude::*;
 
 use crate::game_object::GameObject;
-use crate::lib::texture::Texture2D;
+use crate::simulation::Simulation;
 
 const SIZE: Vector2<f32> = vec2(60.0, 20.0);
-const VELOCITY: Vector2<f32> = vec2(0.0, 1.0);
+// falling speed in pixels per second
+const VELOCITY: Vector2<f32> = vec2(0.0, 60.0);
+// Most balls in play at once, multiball doesn't split any further
+pub const MAX_BALLS: usize = 12;
+// Angle in radians between the balls a multiball power-up splits a ball into
+const MULTIBALL_SPREAD: f32 = 0.35;
 
+// What picking up a power-up does while its effect is still active
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Stacking {
+    // restarts the timer of the active effect
+    Refresh,
+    // adds another layer of the effect with its own timer, up to `max` layers,
+    // past that it restarts the layer closest to running out
+    Stack { max: u32 },
+    // ends the effects of the other power-ups in the same group, refreshes its own
+    Exclusive { group: &'static str },
+}
+
+// One applied layer of a timed power-up effect, undone once its time runs out
+#[derive(Copy, Clone, Debug)]
+pub struct ActiveEffect {
+    pub kind: &'static PowerUpKind,
+    // seconds left
+    pub duration: f32,
+}
+
+// Everything that makes up one kind of power-up
+#[derive(Debug)]
+pub struct PowerUpKind {
+    // how level files refer to it
+    pub name: &'static str,
+    pub texture: &'static str,
+    pub color: Vector3<f32>,
+    // seconds it lasts once picked up, 0.0 if it only takes effect once
+    pub duration: f32,
+    pub stacking: Stacking,
+    // drop chance of a breakable brick unless its level says otherwise, one in this many
+    pub chance: u32,
+    // applies a single layer of the effect, `deactivate` undoes exactly that layer
+    pub activate: fn(&mut Simulation),
+    pub deactivate: fn(&mut Simulation),
+}
+
+impl PowerUpKind {
+    // name the texture is registered under with the resource manager
+    pub fn texture_name(&self) -> String {
+        format!("powerup_{}", self.name)
+    }
+}
+
+// Every power-up in the game, bricks try to drop them in this order
+pub static POWER_UP_KINDS: &[PowerUpKind] = &[
+    PowerUpKind {
+        name: "sticky",
+        texture: "resources/textures/powerup_sticky.png",
+        color: vec3(1.0, 0.5, 1.0),
+        duration: 15.0,
+        stacking: Stacking::Refresh,
+        chance: 75,
+        activate: activate_sticky,
+        deactivate: deactivate_sticky,
+    },
+    PowerUpKind {
+        name: "speed",
+        texture: "resources/textures/powerup_speed.png",
+        color: vec3(0.5, 0.5, 1.0),
+        duration: 15.0,
+        stacking: Stacking::Stack { max: 3 },
+        chance: 75,
+        activate: activate_speed,
+        deactivate: deactivate_speed,
+    },
+    PowerUpKind {
+        name: "passthrough",
+        texture: "resources/textures/powerup_passthrough.png",
+        color: vec3(0.5, 1.0, 0.5),
+        duration: 10.0,
+        stacking: Stacking::Refresh,
+        chance: 75,
+        activate: activate_passthrough,
+        deactivate: deactivate_passthrough,
+    },
+    PowerUpKind {
+        name: "increase",
+        texture: "resources/textures/powerup_increase.png",
+        color: vec3(1.0, 0.6, 0.4),
+        duration: 15.0,
+        stacking: Stacking::Stack { max: 3 },
+        chance: 75,
+        activate: activate_increase,
+        deactivate: deactivate_increase,
+    },
+    PowerUpKind {
+        name: "multiball",
+        texture: "resources/textures/powerup_multiball.png",
+        color: vec3(0.4, 0.9, 1.0),
+        duration: 0.0,
+        stacking: Stacking::Refresh,
+        chance: 75,
+        activate: activate_multiball,
+        deactivate: no_effect,
+    },
+    PowerUpKind {
+        name: "laser",
+        texture: "resources/textures/powerup_laser.png",
+        color: vec3(1.0, 0.4, 0.7),
+        duration: 10.0,
+        stacking: Stacking::Refresh,
+        chance: 75,
+        activate: activate_laser,
+        deactivate: deactivate_laser,
+    },
+    PowerUpKind {
+        name: "magnet",
+        texture: "resources/textures/powerup_magnet.png",
+        color: vec3(0.9, 0.9, 0.4),
+        duration: 15.0,
+        stacking: Stacking::Refresh,
+        chance: 75,
+        activate: activate_magnet,
+        deactivate: deactivate_magnet,
+    },
+    PowerUpKind {
+        name: "shield",
+        texture: "resources/textures/powerup_shield.png",
+        color: vec3(0.3, 0.7, 0.9),
+        duration: 0.0,
+        stacking: Stacking::Refresh,
+        chance: 75,
+        activate: activate_shield,
+        deactivate: no_effect,
+    },
+    PowerUpKind {
+        name: "slow",
+        texture: "resources/textures/powerup_slow.png",
+        color: vec3(0.6, 0.9, 0.8),
+        duration: 10.0,
+        stacking: Stacking::Refresh,
+        chance: 75,
+        activate: activate_slow,
+        deactivate: deactivate_slow,
+    },
+    PowerUpKind {
+        name: "life",
+        texture: "resources/textures/powerup_life.png",
+        color: vec3(1.0, 0.85, 0.3),
+        duration: 0.0,
+        stacking: Stacking::Refresh,
+        chance: 150,
+        activate: activate_life,
+        deactivate: no_effect,
+    },
+    PowerUpKind {
+        name: "confuse",
+        texture: "resources/textures/powerup_confuse.png",
+        color: vec3(1.0, 0.3, 0.3),
+        duration: 15.0,
+        stacking: Stacking::Exclusive { group: "screen" },
+        chance: 15,
+        activate: activate_confuse,
+        deactivate: deactivate_confuse,
+    },
+    PowerUpKind {
+        name: "chaos",
+        texture: "resources/textures/powerup_chaos.png",
+        color: vec3(0.9, 0.25, 0.25),
+        duration: 15.0,
+        stacking: Stacking::Exclusive { group: "screen" },
+        chance: 15,
+        activate: activate_chaos,
+        deactivate: deactivate_chaos,
+    },
+    PowerUpKind {
+        name: "shrink",
+        texture: "resources/textures/powerup_shrink.png",
+        color: vec3(0.8, 0.45, 0.2),
+        duration: 10.0,
+        stacking: Stacking::Refresh,
+        chance: 15,
+        activate: activate_shrink,
+        deactivate: deactivate_shrink,
+    },
+];
+
+// looks up a power-up kind by the name level files use
+pub fn find_power_up_kind(name: &str) -> Option<&'static PowerUpKind> {
+    POWER_UP_KINDS.iter().find(|kind| kind.name == name)
+}
+
+// A power-up falling towards the paddle
 #[derive(Clone, Debug)]
 pub struct PowerUp {
     pub game_object: GameObject,
-    // powerup state
-    pub pw_type: String,
-    pub duration: f32,
-    pub activated: bool,
+    pub kind: &'static PowerUpKind,
 }
 
 impl PowerUp {
-    pub fn new(pos: Vector2<f32>, color: Vector3<f32>, sprite: Texture2D, pw_type: &str, duration: f32, activated: bool) -> Self {
+    pub fn new(pos: Vector2<f32>, kind: &'static PowerUpKind) -> Self {
         PowerUp {
             game_object: GameObject::new(
-                pos, 
-                SIZE, 
-                VELOCITY, 
-                color, 
-                sprite
+                pos,
+                SIZE,
+                VELOCITY,
+                kind.color
             ),
-            pw_type: pw_type.to_string(),
-            duration: duration,
-            activated: activated            
+            kind: kind,
         }
     }
-}
\ No newline at end of file
+}
+
+fn no_effect(_sim: &mut Simulation) {}
+
+fn activate_sticky(sim: &mut Simulation) {
+    for ball in sim.balls.iter_mut() {
+        ball.sticky = true;
+    }
+}
+
+fn deactivate_sticky(sim: &mut Simulation) {
+    for ball in sim.balls.iter_mut() {
+        ball.sticky = false;
+    }
+}
+
+fn activate_speed(sim: &mut Simulation) {
+    for ball in sim.balls.iter_mut() {
+        ball.game_object.velocity *= 1.2;
+    }
+}
+
+fn deactivate_speed(sim: &mut Simulation) {
+    for ball in sim.balls.iter_mut() {
+        ball.game_object.velocity /= 1.2;
+    }
+}
+
+fn activate_passthrough(sim: &mut Simulation) {
+    for ball in sim.balls.iter_mut() {
+        ball.passthrough = true;
+    }
+}
+
+fn deactivate_passthrough(sim: &mut Simulation) {
+    for ball in sim.balls.iter_mut() {
+        ball.passthrough = false;
+    }
+}
+
+fn activate_increase(sim: &mut Simulation) {
+    sim.player.size.x += 50.0;
+}
+
+fn deactivate_increase(sim: &mut Simulation) {
+    sim.player.size.x -= 50.0;
+}
+
+// splits every ball in play into three, fanned out around its direction
+fn activate_multiball(sim: &mut Simulation) {
+    let mut new_balls = Vec::new();
+    for i in 0..sim.balls.len() {
+        let ball = sim.balls[i];
+        for angle in [-MULTIBALL_SPREAD, MULTIBALL_SPREAD].iter() {
+            if sim.balls.len() + new_balls.len() >= MAX_BALLS {
+                break;
+            }
+            let mut new_ball = ball;
+            new_ball.stuck = false;
+            new_ball.game_object.id = sim.new_object_id();
+            new_ball.game_object.velocity = Basis2::from_angle(Rad(*angle)).rotate_vector(ball.game_object.velocity);
+            new_balls.push(new_ball);
+        }
+    }
+    sim.balls.extend(new_balls);
+}
+
+fn activate_laser(sim: &mut Simulation) {
+    sim.laser = true;
+}
+
+fn deactivate_laser(sim: &mut Simulation) {
+    sim.laser = false;
+}
+
+fn activate_magnet(sim: &mut Simulation) {
+    sim.magnet = true;
+}
+
+fn deactivate_magnet(sim: &mut Simulation) {
+    sim.magnet = false;
+}
+
+fn activate_shield(sim: &mut Simulation) {
+    sim.shield = true;
+}
+
+fn activate_slow(sim: &mut Simulation) {
+    for ball in sim.balls.iter_mut() {
+        ball.game_object.velocity *= 0.7;
+    }
+}
+
+fn deactivate_slow(sim: &mut Simulation) {
+    for ball in sim.balls.iter_mut() {
+        ball.game_object.velocity /= 0.7;
+    }
+}
+
+fn activate_shrink(sim: &mut Simulation) {
+    sim.player.size.x -= 40.0;
+}
+
+fn deactivate_shrink(sim: &mut Simulation) {
+    sim.player.size.x += 40.0;
+}
+
+fn activate_life(sim: &mut Simulation) {
+    sim.award_life();
+}
+
+fn activate_confuse(sim: &mut Simulation) {
+    sim.effects.confuse = true;
+}
+
+fn deactivate_confuse(sim: &mut Simulation) {
+    sim.effects.confuse = false;
+}
+
+fn activate_chaos(sim: &mut Simulation) {
+    sim.effects.chaos = true;
+}
+
+fn deactivate_chaos(sim: &mut Simulation) {
+    sim.effects.chaos = false;
+}
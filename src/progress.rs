@@ -0,0 +1,76 @@
+use std::fs;
+use std::path::Path;
+
+use crate::settings::read_key_values;
+
+// Campaign progress that is kept between runs
+#[derive(Clone, Debug)]
+pub struct Progress {
+    // index of the furthest level the player has reached
+    pub highest_level: usize,
+    // whether the last level of the campaign has been cleared
+    pub completed: bool,
+    file_path: String,
+}
+
+impl Progress {
+    pub fn new(file_path: &str) -> Self {
+        Progress {
+            highest_level: 0,
+            completed: false,
+            file_path: file_path.to_string(),
+        }
+    }
+
+    // loads the saved progress, starting a fresh campaign if there is none
+    pub fn load(file_path: &str) -> Self {
+        let mut progress = Progress::new(file_path);
+
+        if let Some(entries) = read_key_values(file_path) {
+            for (line, key, value) in entries {
+                match key.as_str() {
+                    "highest_level" => match value.parse::<usize>() {
+                        Ok(level) => progress.highest_level = level,
+                        Err(_) => println!("ERROR::PROGRESS: Invalid level `{}` on line {}", value, line),
+                    },
+                    "completed" => match value.parse::<bool>() {
+                        Ok(completed) => progress.completed = completed,
+                        Err(_) => println!("ERROR::PROGRESS: Invalid flag `{}` on line {}", value, line),
+                    },
+                    _ => println!("ERROR::PROGRESS: Unknown entry `{}` on line {}", key, line),
+                }
+            }
+        }
+
+        progress
+    }
+
+    pub fn save(&self) {
+        if let Some(directory) = Path::new(&self.file_path).parent() {
+            if fs::create_dir_all(directory).is_err() {
+                println!("ERROR::PROGRESS: Failed to create {}", directory.display());
+                return;
+            }
+        }
+
+        let contents = format!("highest_level = {}\ncompleted = {}\n", self.highest_level, self.completed);
+        if fs::write(&self.file_path, contents).is_err() {
+            println!("ERROR::PROGRESS: Failed to write {}", self.file_path);
+        }
+    }
+
+    // records that a level has been reached, saving if that is further than before
+    pub fn reach_level(&mut self, level: usize) {
+        if level > self.highest_level {
+            self.highest_level = level;
+            self.save();
+        }
+    }
+
+    pub fn complete_campaign(&mut self) {
+        if !self.completed {
+            self.completed = true;
+            self.save();
+        }
+    }
+}
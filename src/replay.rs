@@ -0,0 +1,422 @@
+use std::fs;
+use std::path::Path;
+
+use crate::game_level::GameLevel;
+use crate::settings::read_key_values;
+use crate::simulation::{PlayerInput, Simulation};
+
+// Slowest and fastest playback speeds
+pub const MIN_SPEED: f32 = 0.25;
+pub const MAX_SPEED: f32 = 8.0;
+// Simulation steps between two checkpoints kept for scrubbing
+const CHECKPOINT_INTERVAL: usize = 600;
+
+// A recorded game: everything its outcome depends on, plus the paddle controls of every step
+#[derive(Clone, Debug)]
+pub struct Replay {
+    pub seed: u64,
+    pub update_rate: f32,
+    pub width: u32,
+    pub height: u32,
+    pub lives: u32,
+    pub max_lives: u32,
+    pub extra_life_score: u32,
+    // files of the campaign levels in order, and the one the game started at
+    pub levels: Vec<String>,
+    pub level: usize,
+    pub inputs: Vec<PlayerInput>,
+    // score at the end of the game
+    pub score: u32,
+}
+
+impl Replay {
+    // starts recording a new game that is about to be played on `sim`
+    pub fn record(sim: &Simulation, update_rate: f32) -> Self {
+        Replay {
+            seed: sim.seed,
+            update_rate: update_rate,
+            width: sim.width,
+            height: sim.height,
+            lives: sim.initial_lives,
+            max_lives: sim.max_lives,
+            extra_life_score: sim.extra_life_score,
+            levels: sim.levels.iter().map(|level| level.file_path.clone()).collect(),
+            level: sim.actual_level,
+            inputs: Vec::new(),
+            score: 0,
+        }
+    }
+
+    // loads a replay file, None (after reporting why) if it can't be read
+    pub fn load(file_path: &str) -> Option<Self> {
+        let entries = match read_key_values(file_path) {
+            Some(entries) => entries,
+            None => {
+                println!("ERROR::REPLAY: Failed to open {}", file_path);
+                return None;
+            }
+        };
+
+        let mut replay = Replay {
+            seed: 0,
+            update_rate: 0.0,
+            width: 0,
+            height: 0,
+            lives: 0,
+            max_lives: 0,
+            extra_life_score: 0,
+            levels: Vec::new(),
+            level: 0,
+            inputs: Vec::new(),
+            score: 0,
+        };
+        for (line, key, value) in entries {
+            let valid = match key.as_str() {
+                "seed" => value.parse().map(|seed| replay.seed = seed).is_ok(),
+                "update_rate" => value.parse().map(|rate| replay.update_rate = rate).is_ok(),
+                "width" => value.parse().map(|width| replay.width = width).is_ok(),
+                "height" => value.parse().map(|height| replay.height = height).is_ok(),
+                "lives" => value.parse().map(|lives| replay.lives = lives).is_ok(),
+                "max_lives" => value.parse().map(|lives| replay.max_lives = lives).is_ok(),
+                "extra_life_score" => value.parse().map(|score| replay.extra_life_score = score).is_ok(),
+                "levels" => {
+                    replay.levels = value.split(',').map(|level| level.trim().to_string()).collect();
+                    true
+                },
+                "level" => value.parse().map(|level| replay.level = level).is_ok(),
+                "score" => value.parse().map(|score| replay.score = score).is_ok(),
+                "inputs" => decode_inputs(&value).map(|inputs| replay.inputs = inputs).is_some(),
+                _ => {
+                    println!("ERROR::REPLAY: Unknown entry `{}` on line {}", key, line);
+                    continue;
+                }
+            };
+            if !valid {
+                println!("ERROR::REPLAY: Invalid value for `{}` on line {}", key, line);
+                return None;
+            }
+        }
+
+        if replay.update_rate <= 0.0 || replay.level >= replay.levels.len() {
+            println!("ERROR::REPLAY: {} is missing its update rate or levels", file_path);
+            return None;
+        }
+        Some(replay)
+    }
+
+    pub fn save(&self, file_path: &str) {
+        if let Some(directory) = Path::new(file_path).parent() {
+            if fs::create_dir_all(directory).is_err() {
+                println!("ERROR::REPLAY: Failed to create {}", directory.display());
+                return;
+            }
+        }
+
+        let contents = format!(
+            "seed = {}\nupdate_rate = {}\nwidth = {}\nheight = {}\nlives = {}\nmax_lives = {}\nextra_life_score = {}\nlevels = {}\nlevel = {}\nscore = {}\ninputs = {}\n",
+            self.seed, self.update_rate, self.width, self.height, self.lives, self.max_lives,
+            self.extra_life_score, self.levels.join(","), self.level, self.score, encode_inputs(&self.inputs)
+        );
+        if fs::write(file_path, contents).is_err() {
+            println!("ERROR::REPLAY: Failed to write {}", file_path);
+        }
+    }
+
+    // sets up a simulation at the start of the recorded game, with the settings it was recorded with,
+    // whatever game and level it was on before
+    pub fn start(&self, sim: &mut Simulation) {
+        sim.seed = self.seed;
+        sim.initial_lives = self.lives;
+        sim.max_lives = self.max_lives;
+        sim.extra_life_score = self.extra_life_score;
+        sim.new_game();
+        sim.set_level(self.level);
+    }
+
+    // seconds of play
+    pub fn duration(&self) -> f32 {
+        self.inputs.len() as f32 / self.update_rate
+    }
+
+    // plays the whole replay without a window, returning the final score
+    pub fn verify(&self) -> Result<u32, String> {
+        let mut sim = self.new_simulation()?;
+        let dt = 1.0 / self.update_rate;
+        for input in self.inputs.iter() {
+            if is_finished(&sim) {
+                return Err("the game ended before the recorded input did".to_string());
+            }
+            step(&mut sim, input, dt);
+        }
+        Ok(sim.score.points)
+    }
+
+    // a simulation at the start of the recorded game, with its levels loaded from their files
+    fn new_simulation(&self) -> Result<Simulation, String> {
+        let mut sim = Simulation::new(self.width, self.height);
+        for file_path in self.levels.iter() {
+            let mut level = GameLevel::new();
+            level.load(file_path, self.width, self.height / 2)
+                .map_err(|error| format!("failed to load {}: {}", file_path, error))?;
+            sim.levels.push(level);
+        }
+        self.start(&mut sim);
+        Ok(sim)
+    }
+}
+
+// Playing a replay back in the game, with checkpoints to scrub through it
+pub struct Playback {
+    pub replay: Replay,
+    // index of the next step to play
+    pub tick: usize,
+    pub speed: f32,
+    // copies of the simulation before every CHECKPOINT_INTERVAL-th step, in order
+    checkpoints: Vec<(usize, Simulation)>,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        Playback {
+            replay: replay,
+            tick: 0,
+            speed: 1.0,
+            checkpoints: Vec::new(),
+        }
+    }
+
+    // controls of the next step, None once the recording has run out
+    pub fn next_input(&mut self, sim: &Simulation) -> Option<PlayerInput> {
+        let input = *self.replay.inputs.get(self.tick)?;
+        self.checkpoint(sim);
+        self.tick += 1;
+        Some(input)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.tick >= self.replay.inputs.len()
+    }
+
+    // seconds played so far
+    pub fn time(&self) -> f32 {
+        self.tick as f32 / self.replay.update_rate
+    }
+
+    // halves or doubles the playback speed, within its limits
+    pub fn change_speed(&mut self, faster: bool) {
+        let speed = if faster { self.speed * 2.0 } else { self.speed / 2.0 };
+        self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+    }
+
+    // moves the simulation to the start of step `tick`, from the last checkpoint before it,
+    // a level that was cleared (the level complete screen may be showing) is left first
+    pub fn seek(&mut self, sim: &mut Simulation, tick: usize) {
+        let tick = tick.min(self.replay.inputs.len());
+        if tick < self.tick {
+            match self.checkpoints.iter().rev().find(|(checkpoint, _)| *checkpoint <= tick) {
+                Some((checkpoint, saved)) => {
+                    self.tick = *checkpoint;
+                    *sim = saved.clone();
+                },
+                None => return,
+            }
+        }
+
+        // the level complete screen starts the next level without using up a step of input
+        advance_cleared_level(sim);
+        let dt = 1.0 / self.replay.update_rate;
+        while self.tick < tick && !is_finished(sim) {
+            if let Some(input) = self.next_input(sim) {
+                step(sim, &input, dt);
+            }
+        }
+    }
+
+    fn checkpoint(&mut self, sim: &Simulation) {
+        let last = self.checkpoints.last().map(|(tick, _)| *tick);
+        if self.tick % CHECKPOINT_INTERVAL == 0 && last.map_or(true, |last| last < self.tick) {
+            self.checkpoints.push((self.tick, sim.clone()));
+        }
+    }
+}
+
+// whether the game is over, either lost or won
+fn is_finished(sim: &Simulation) -> bool {
+    sim.is_game_over() || (sim.is_level_completed() && sim.is_last_level())
+}
+
+// advances a simulation by one step the way the game does, including moving on
+// to the next level as the level complete screen would
+fn step(sim: &mut Simulation, input: &PlayerInput, dt: f32) {
+    sim.process_input(input, dt);
+    sim.update(dt);
+    advance_cleared_level(sim);
+}
+
+// starts the next level once the current one is cleared, as leaving the level complete screen does
+fn advance_cleared_level(sim: &mut Simulation) {
+    if sim.is_level_completed() && !sim.is_last_level() && !sim.is_game_over() {
+        sim.reset_player();
+        sim.set_level(sim.actual_level + 1);
+    }
+}
+
+// paddle controls of a step as bits: full speed left, full speed right, launch,
+// followed by `@steer` when the paddle moves at any other speed
+fn encode_input(input: &PlayerInput) -> String {
+    let launch = (input.launch as u8) << 2;
+    if input.steer == 0.0 {
+        format!("{}", launch)
+    } else if input.steer == -1.0 {
+        format!("{}", launch | 1)
+    } else if input.steer == 1.0 {
+        format!("{}", launch | 2)
+    } else {
+        format!("{}@{}", launch, input.steer)
+    }
+}
+
+fn decode_input(text: &str) -> Option<PlayerInput> {
+    let (bits, steer) = match text.split_once('@') {
+        Some((bits, steer)) => (bits.parse::<u8>().ok()?, steer.parse::<f32>().ok()?),
+        None => (text.parse::<u8>().ok()?, 0.0),
+    };
+    let steer = if bits & 1 != 0 { -1.0 } else if bits & 2 != 0 { 1.0 } else { steer };
+    Some(PlayerInput { steer: steer, launch: bits & 4 != 0 })
+}
+
+// run-length encodes the controls as `count*input` entries
+fn encode_inputs(inputs: &[PlayerInput]) -> String {
+    let mut runs: Vec<(usize, PlayerInput)> = Vec::new();
+    for input in inputs {
+        match runs.last_mut() {
+            Some((count, last)) if last == input => *count += 1,
+            _ => runs.push((1, *input)),
+        }
+    }
+    runs.iter().map(|(count, input)| format!("{}*{}", count, encode_input(input))).collect::<Vec<String>>().join(" ")
+}
+
+fn decode_inputs(text: &str) -> Option<Vec<PlayerInput>> {
+    let mut inputs = Vec::new();
+    for run in text.split_whitespace() {
+        let (count, input) = run.split_once('*')?;
+        let count: usize = count.parse().ok()?;
+        let input = decode_input(input)?;
+        inputs.extend(std::iter::repeat(input).take(count));
+    }
+    Some(inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UPDATE_RATE: f32 = 120.0;
+
+    // a directory of its own for the files a test writes
+    fn test_directory(name: &str) -> String {
+        let directory = std::env::temp_dir().join(format!("breakout-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        directory.to_string_lossy().to_string()
+    }
+
+    // records `inputs` played headless on `levels` from `first_level`, the way the game records a game
+    fn record(levels: &[String], first_level: usize, inputs: &[PlayerInput]) -> Replay {
+        let mut sim = Simulation::new(800, 600);
+        sim.seed = 3;
+        for file_path in levels {
+            let mut level = GameLevel::new();
+            level.load(file_path, 800, 300).unwrap();
+            sim.levels.push(level);
+        }
+        sim.actual_level = first_level;
+        let mut replay = Replay::record(&sim, UPDATE_RATE);
+        let mut sim = replay.new_simulation().unwrap();
+        for input in inputs {
+            if is_finished(&sim) {
+                break;
+            }
+            step(&mut sim, input, 1.0 / UPDATE_RATE);
+            replay.inputs.push(*input);
+        }
+        replay.score = sim.score.points;
+        replay
+    }
+
+    // launches straight away and steers back and forth
+    fn scripted_inputs(count: usize) -> Vec<PlayerInput> {
+        (0..count).map(|i| PlayerInput { steer: ((i / 50) % 3) as f32 - 1.0, launch: i % 7 == 0 }).collect()
+    }
+
+    #[test]
+    fn saved_replay_verifies_to_its_recorded_score() {
+        let replay = record(&["resources/levels/one.lvl".to_string()], 0, &scripted_inputs(120 * 20));
+        assert!(replay.score > 0);
+
+        let file_path = format!("{}/last.replay", test_directory("replay-save"));
+        replay.save(&file_path);
+        let loaded = Replay::load(&file_path).unwrap();
+        assert_eq!(loaded.inputs, replay.inputs);
+        assert_eq!(loaded.verify(), Ok(replay.score));
+    }
+
+    // two levels of a single brick, the ball launched straight up clears each in a moment
+    fn single_brick_levels(name: &str) -> Vec<String> {
+        let directory = test_directory(name);
+        ["first.lvl", "second.lvl"].iter().map(|file| {
+            let file_path = format!("{}/{}", directory, file);
+            fs::write(&file_path, "2\n").unwrap();
+            file_path
+        }).collect()
+    }
+
+    #[test]
+    fn replay_started_from_the_win_screen_plays_from_its_own_level() {
+        let levels = single_brick_levels("replay-win");
+        let launch = PlayerInput { steer: 0.0, launch: true };
+        let replay = record(&levels, 1, &vec![launch; 120 * 10]);
+        assert!(replay.score > 0);
+
+        // win the campaign, then leave the win screen the way the game does before the replay starts
+        let mut sim = replay.new_simulation().unwrap();
+        let mut playback = Playback::new(replay.clone());
+        playback.seek(&mut sim, replay.inputs.len());
+        assert!(sim.is_level_completed() && sim.is_last_level());
+        sim.set_level(0);
+        replay.start(&mut sim);
+        assert_eq!(sim.actual_level, 1);
+
+        let mut playback = Playback::new(replay.clone());
+        playback.seek(&mut sim, replay.inputs.len());
+        assert!(sim.is_level_completed());
+        assert_eq!(sim.score.points, replay.score);
+    }
+
+    #[test]
+    fn scrubbing_from_the_level_complete_screen_stays_in_sync() {
+        let levels = single_brick_levels("replay-scrub");
+        let launch = PlayerInput { steer: 0.0, launch: true };
+        let replay = record(&levels, 0, &vec![launch; 120 * 10]);
+        let dt = 1.0 / UPDATE_RATE;
+
+        // play the first level as the game does, which stops stepping once it is cleared
+        let mut playback = Playback::new(replay.clone());
+        let mut sim = replay.new_simulation().unwrap();
+        while !sim.is_level_completed() {
+            let input = playback.next_input(&sim).unwrap();
+            sim.process_input(&input, dt);
+            sim.update(dt);
+        }
+        sim.reset_player();
+        let tick = playback.tick + 20;
+        playback.seek(&mut sim, tick);
+
+        // the same point of the replay reached by stepping straight through it
+        let mut expected = replay.new_simulation().unwrap();
+        Playback::new(replay.clone()).seek(&mut expected, tick);
+        assert_eq!(sim.actual_level, 1);
+        assert_eq!(sim.level_time, expected.level_time);
+        assert_eq!(sim.balls[0].game_object.position, expected.balls[0].game_object.position);
+    }
+}
@@ -1,16 +1,18 @@
 use std::collections::HashMap;
 use std::path::Path;
 
+use image::ImageError;
+
 use crate::lib::shader::Shader;
 use crate::lib::texture::Texture2D;
 
-pub struct ResourceManager<'a> {
-    pub shaders: HashMap<&'a str, Shader>,
-    pub textures: HashMap<&'a str, Texture2D>,
+pub struct ResourceManager {
+    pub shaders: HashMap<String, Shader>,
+    pub textures: HashMap<String, Texture2D>,
 }
 
-impl<'a> ResourceManager<'a> {
-    pub fn new() -> ResourceManager<'a> {
+impl ResourceManager {
+    pub fn new() -> ResourceManager {
         let resource_manager = ResourceManager {
             shaders: HashMap::new(),
             textures: HashMap::new(),
@@ -19,11 +21,11 @@ impl<'a> ResourceManager<'a> {
         resource_manager
     }
 
-    pub fn load_shader(&mut self, vertex_path: &str, fragment_path: &str, name: &'a str) -> Shader {
+    pub fn load_shader(&mut self, vertex_path: &str, fragment_path: &str, name: &str) -> Shader {
         // build and compile our shader program
         // ------------------------------------
         let shader = Shader::new(vertex_path, fragment_path);
-        self.shaders.insert(name, shader);
+        self.shaders.insert(name.to_string(), shader);
 
         shader
     }
@@ -34,8 +36,13 @@ impl<'a> ResourceManager<'a> {
     //     *shader
     // }
 
-    pub fn load_texture(&mut self, path: &str, alpha: bool, name: &'a str) -> Texture2D {
-        let img = image::open(&Path::new(path)).expect("Failed to load texture");
+    pub fn load_texture(&mut self, path: &str, alpha: bool, name: &str) -> Texture2D {
+        self.try_load_texture(path, alpha, name).expect("Failed to load texture")
+    }
+
+    // loads a texture that may be missing or broken, e.g. one named by a level file
+    pub fn try_load_texture(&mut self, path: &str, alpha: bool, name: &str) -> Result<Texture2D, ImageError> {
+        let img = image::open(&Path::new(path))?;
         let data = img.clone().into_bytes();
         
         let mut texture = Texture2D::new();
@@ -49,9 +56,13 @@ impl<'a> ResourceManager<'a> {
             texture.generate(img.width(), img.height(), data);
         }
 
-        self.textures.insert(name, texture);
+        self.textures.insert(name.to_string(), texture);
+
+        Ok(texture)
+    }
 
-        texture
+    pub fn has_texture(&self, name: &str) -> bool {
+        self.textures.contains_key(name)
     }
 
     pub fn get_texture(&self, name: &str) -> Texture2D {
@@ -0,0 +1,49 @@
+use std::cmp;
+
+// Number of consecutive bricks needed to raise the combo multiplier by one
+const COMBO_STEP: u32 = 3;
+// Highest combo multiplier
+const MAX_MULTIPLIER: u32 = 5;
+
+// Points scored in the current game
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Score {
+    pub points: u32,
+    // bricks hit since the ball last touched the paddle
+    pub combo: u32,
+    // longest combo of the current game
+    pub best_combo: u32,
+}
+
+impl Score {
+    pub fn new() -> Self {
+        Score::default()
+    }
+
+    pub fn multiplier(&self) -> u32 {
+        cmp::min(1 + self.combo / COMBO_STEP, MAX_MULTIPLIER)
+    }
+
+    // adds a brick hit to the combo and returns the points it was worth
+    pub fn brick_hit(&mut self, points: u32) -> u32 {
+        self.combo += 1;
+        self.best_combo = cmp::max(self.best_combo, self.combo);
+
+        let awarded = points * self.multiplier();
+        self.points += awarded;
+        awarded
+    }
+
+    // flat bonus that is not affected by the combo
+    pub fn add_bonus(&mut self, points: u32) {
+        self.points += points;
+    }
+
+    pub fn break_combo(&mut self) {
+        self.combo = 0;
+    }
+
+    pub fn reset(&mut self) {
+        *self = Score::new();
+    }
+}
@@ -0,0 +1,125 @@
+use std::fs::File;
+use std::io::{prelude::*, BufReader};
+
+// Default simulation steps per second
+const UPDATE_RATE: f32 = 120.0;
+// Default maximum number of catch-up steps per frame
+const MAX_CATCH_UP_STEPS: u32 = 8;
+// Default number of lives at the start of a game
+const LIVES: u32 = 3;
+// Default maximum number of lives that can be collected
+const MAX_LIVES: u32 = 5;
+// Default score interval at which an extra life is awarded (0 disables)
+const EXTRA_LIFE_SCORE: u32 = 5000;
+// Default share of the stick's travel around its center that is ignored
+const GAMEPAD_DEADZONE: f32 = 0.2;
+
+// User configurable options, read from a simple `key = value` file
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub update_rate: f32,
+    pub max_catch_up_steps: u32,
+    pub lives: u32,
+    pub max_lives: u32,
+    pub extra_life_score: u32,
+    // seed of every game, a new random one per game if not set
+    pub seed: Option<u64>,
+    pub gamepad_deadzone: f32,
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        Settings {
+            update_rate: UPDATE_RATE,
+            max_catch_up_steps: MAX_CATCH_UP_STEPS,
+            lives: LIVES,
+            max_lives: MAX_LIVES,
+            extra_life_score: EXTRA_LIFE_SCORE,
+            seed: None,
+            gamepad_deadzone: GAMEPAD_DEADZONE,
+        }
+    }
+
+    // loads the settings from file, keeping the defaults for anything missing or invalid
+    pub fn load(file_path: &str) -> Self {
+        let mut settings = Settings::new();
+
+        match read_key_values(file_path) {
+            Some(entries) => {
+                for (line, key, value) in entries {
+                    settings.set(&key, &value, line);
+                }
+            },
+            None => println!("ERROR::SETTINGS: Failed to open {}, using defaults", file_path),
+        }
+
+        settings
+    }
+
+    fn set(&mut self, key: &str, value: &str, line: usize) {
+        let valid = match key {
+            "update_rate" => match value.parse::<f32>() {
+                Ok(rate) if rate > 0.0 => { self.update_rate = rate; true },
+                _ => false,
+            },
+            "max_catch_up_steps" => match value.parse::<u32>() {
+                Ok(steps) if steps > 0 => { self.max_catch_up_steps = steps; true },
+                _ => false,
+            },
+            "lives" => match value.parse::<u32>() {
+                Ok(lives) if lives > 0 => { self.lives = lives; true },
+                _ => false,
+            },
+            "max_lives" => match value.parse::<u32>() {
+                Ok(lives) if lives > 0 => { self.max_lives = lives; true },
+                _ => false,
+            },
+            "extra_life_score" => match value.parse::<u32>() {
+                Ok(score) => { self.extra_life_score = score; true },
+                _ => false,
+            },
+            "seed" => match value.parse::<u64>() {
+                Ok(seed) => { self.seed = Some(seed); true },
+                _ => false,
+            },
+            "gamepad_deadzone" => match value.parse::<f32>() {
+                Ok(deadzone) if (0.0..1.0).contains(&deadzone) => { self.gamepad_deadzone = deadzone; true },
+                _ => false,
+            },
+            _ => {
+                println!("ERROR::SETTINGS: Unknown setting `{}` on line {}", key, line);
+                return;
+            }
+        };
+
+        if !valid {
+            println!("ERROR::SETTINGS: Invalid value `{}` for `{}` on line {}", value, key, line);
+        }
+    }
+}
+
+// reads `key = value` lines (skipping blanks and `#` comments) as (line number, key, value)
+pub fn read_key_values(file_path: &str) -> Option<Vec<(usize, String, String)>> {
+    let file = File::open(file_path).ok()?;
+    let mut entries = Vec::new();
+
+    let reader = BufReader::new(file);
+    for (number, line) in reader.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let line = line.trim();
+        // skip empty lines and comments
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match line.split_once('=') {
+            Some((key, value)) => entries.push((number + 1, key.trim().to_string(), value.trim().to_string())),
+            None => println!("ERROR::SETTINGS: Expected `key = value` on line {} of {}", number + 1, file_path),
+        }
+    }
+
+    Some(entries)
+}
@@ -0,0 +1,1034 @@
+use cgmath::{vec2, vec3, Vector2};
+use cgmath::prelude::*;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+use crate::ball::Ball;
+use crate::collision::{check_square_collision, clamp, length, reflect, sweep_circle_collision, Hit};
+use crate::game_level::GameLevel;
+use crate::level_format::{find_brick_type, PowerUpDrop};
+use crate::game_object::GameObject;
+use crate::power_up::{find_power_up_kind, ActiveEffect, PowerUp, PowerUpKind, Stacking};
+use crate::score::Score;
+
+// Initial size of the player paddle
+pub const PLAYER_SIZE: Vector2<f32> = vec2(100.0, 20.0);
+// Initial velocity of the player paddle
+pub const PLAYER_VELOCITY: f32 = 500.0;
+// Initial velocity of the Ball
+pub const INITIAL_BALL_VELOCITY: Vector2<f32> = vec2(100.0, -350.0);
+// Radius of the ball object
+pub const BALL_RADIUS: f32 = 12.5;
+// Default number of lives at the start of a game
+pub const INITIAL_LIVES: u32 = 3;
+// Bonus for catching a power-up
+pub const POWER_UP_BONUS: u32 = 25;
+// Bonus for clearing a level
+pub const LEVEL_CLEAR_BONUS: u32 = 1000;
+// Bonus for clearing a level within its par time
+pub const PAR_TIME_BONUS: u32 = 500;
+// Most collisions of the ball resolved in a single update, the rest of its motion is dropped
+const MAX_BALL_COLLISIONS: u32 = 16;
+// Size and upward speed in pixels per second of a laser bolt
+const LASER_SIZE: Vector2<f32> = vec2(6.0, 18.0);
+const LASER_VELOCITY: f32 = 700.0;
+// Seconds between two shots of the laser paddle
+const LASER_INTERVAL: f32 = 0.3;
+// How strongly the magnet steers a falling ball towards the middle of the paddle
+const MAGNET_PULL: f32 = 4.0;
+
+// What the ball runs into first while moving
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Obstacle {
+    Wall,
+    Brick(usize),
+    Paddle,
+    Shield,
+}
+
+// Paddle controls held down during an update
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
+pub struct PlayerInput {
+    // paddle speed from -1.0 (full speed left) to 1.0 (full speed right)
+    pub steer: f32,
+    pub launch: bool,
+}
+
+// Post-processing effects requested by the simulation, mirrored by the renderer
+#[derive(Copy, Clone, Default, Debug)]
+pub struct Effects {
+    pub confuse: bool,
+    pub chaos: bool,
+    pub shake: bool,
+}
+
+// Holds the whole game logic (paddle, ball, bricks and power-ups) without
+// touching the window, OpenGL or any texture, so it can run headless
+#[derive(Clone)]
+pub struct Simulation {
+    pub width: u32,
+    pub height: u32,
+    pub player: GameObject,
+    // every ball in play, a life is lost once the last one drains
+    pub balls: Vec<Ball>,
+    pub levels: Vec<GameLevel>,
+    pub actual_level: usize,
+    pub power_ups: Vec<PowerUp>,
+    // layers of timed power-up effects currently applied
+    pub active_effects: Vec<ActiveEffect>,
+    pub effects: Effects,
+    // the paddle fires laser bolts with the launch control
+    pub laser: bool,
+    pub lasers: Vec<GameObject>,
+    laser_cooldown: f32,
+    // id for the next ball, laser bolt or power-up that comes into play
+    next_object_id: u32,
+    // falling balls are pulled towards the paddle
+    pub magnet: bool,
+    // the bottom of the screen bounces the next ball that reaches it
+    pub shield: bool,
+    // lives left, the game is over once the last one is lost
+    pub lives: u32,
+    pub initial_lives: u32,
+    pub max_lives: u32,
+    pub score: Score,
+    // score interval at which an extra life is awarded (0 disables)
+    pub extra_life_score: u32,
+    next_extra_life: u32,
+    // seconds spent in the current level
+    pub level_time: f32,
+    shake_time: f32,
+    // seed of the current game, the same seed and input always play out the same
+    pub seed: u64,
+    // randomness of the game itself, such as which power-ups drop
+    pub rng: ChaCha8Rng,
+    // randomness of anything only drawn, kept apart so that drawing it or not never changes the game
+    pub particle_rng: ChaCha8Rng,
+}
+
+impl Simulation {
+    pub fn new(width: u32, height: u32) -> Self {
+        // Player initialization
+        let player_pos = vec2(
+            width as f32 / 2.0 - PLAYER_SIZE.x / 2.0,
+            height as f32 - PLAYER_SIZE.y
+        );
+        let player = GameObject::new(player_pos, PLAYER_SIZE, vec2(0.0, 0.0), vec3(1.0, 1.0, 1.0));
+
+        // Ball initialization
+        let ball_pos = player_pos + vec2(
+            PLAYER_SIZE.x / 2.0 - BALL_RADIUS,
+            -BALL_RADIUS * 2.0
+        );
+        let ball = Ball::new(ball_pos, BALL_RADIUS, INITIAL_BALL_VELOCITY);
+
+        Simulation {
+            width: width,
+            height: height,
+            player: player,
+            balls: vec![ball],
+            levels: Vec::new(),
+            actual_level: 0,
+            power_ups: Vec::new(),
+            active_effects: Vec::new(),
+            effects: Effects::default(),
+            laser: false,
+            lasers: Vec::new(),
+            laser_cooldown: 0.0,
+            next_object_id: 1,
+            magnet: false,
+            shield: false,
+            lives: INITIAL_LIVES,
+            initial_lives: INITIAL_LIVES,
+            max_lives: INITIAL_LIVES,
+            score: Score::new(),
+            extra_life_score: 0,
+            next_extra_life: 0,
+            level_time: 0.0,
+            shake_time: 0.0,
+            seed: 0,
+            rng: ChaCha8Rng::seed_from_u64(0),
+            particle_rng: particle_rng(0),
+        }
+    }
+
+    pub fn process_input(&mut self, input: &PlayerInput, dt: f32) {
+        let velocity = PLAYER_VELOCITY * input.steer.clamp(-1.0, 1.0) * dt;
+        // move paddle
+        let can_move = if velocity < 0.0 {
+            self.player.position.x >= 0.0
+        } else {
+            self.player.position.x <= self.width as f32 - self.player.size.x
+        };
+        if velocity != 0.0 && can_move {
+            self.player.position.x += velocity;
+            for ball in self.balls.iter_mut().filter(|ball| ball.stuck) {
+                ball.game_object.position.x += velocity;
+            }
+        }
+        if input.launch {
+            for ball in self.balls.iter_mut() {
+                ball.stuck = false;
+            }
+            if self.laser && self.laser_cooldown <= 0.0 {
+                self.fire_lasers();
+            }
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.level_time += dt;
+        // move the balls, bouncing off walls, bricks and the paddle along the way
+        if self.magnet {
+            self.pull_balls(dt);
+        }
+        let mut bricks_destroyed = false;
+        for i in 0..self.balls.len() {
+            bricks_destroyed |= self.move_ball(i, dt);
+        }
+        bricks_destroyed |= self.update_lasers(dt);
+        // clearing the level is worth a bonus, and more so within its par time
+        if bricks_destroyed && self.is_level_completed() {
+            self.score.add_bonus(LEVEL_CLEAR_BONUS);
+            if let Some(par_time) = self.levels[self.actual_level].info.par_time {
+                if self.level_time <= par_time {
+                    self.score.add_bonus(PAR_TIME_BONUS);
+                }
+            }
+        }
+        // check for collisions
+        self.do_collisions();
+        // update PowerUps and their effects
+        self.update_power_ups(dt);
+        self.update_effects(dt);
+        // update effects
+        if self.shake_time > 0.0 {
+            self.shake_time -= dt;
+            if self.shake_time <= 0.0 {
+                self.effects.shake = false;
+            }
+        }
+        // check loss condition, balls that drain are gone but only the last one costs a life
+        let height = self.height as f32;
+        self.balls.retain(|ball| ball.game_object.position.y < height);
+        if self.balls.is_empty() {
+            self.lives = self.lives.saturating_sub(1);
+            self.score.break_combo();
+            // the bricks are only restored once every life is gone
+            if self.is_game_over() {
+                self.reset_level();
+            }
+            self.reset_player();
+        }
+        self.update_tints();
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.lives == 0
+    }
+
+    // starts over with the configured number of lives and no score
+    pub fn new_game(&mut self) {
+        self.rng = ChaCha8Rng::seed_from_u64(self.seed);
+        self.particle_rng = particle_rng(self.seed);
+        self.lives = self.initial_lives;
+        self.score.reset();
+        self.next_extra_life = self.extra_life_score;
+    }
+
+    // grants an extra life, up to the configured maximum
+    pub fn award_life(&mut self) {
+        if self.lives < self.max_lives {
+            self.lives += 1;
+        }
+    }
+
+    pub fn is_level_completed(&self) -> bool {
+        self.levels[self.actual_level].is_completed()
+    }
+
+    pub fn is_last_level(&self) -> bool {
+        self.actual_level + 1 >= self.levels.len()
+    }
+
+    // switches to another level and starts it from scratch
+    pub fn set_level(&mut self, level: usize) {
+        self.actual_level = level;
+        self.reset_level();
+        self.reset_player();
+    }
+
+    pub fn reset_level(&mut self) {
+        self.level_time = 0.0;
+        self.levels[self.actual_level].reset();
+    }
+
+    pub fn reset_player(&mut self) {
+        // also disable all active powerups
+        self.end_effects(|_| true);
+        // reset player/ball stats
+        let player_pos = vec2(
+            self.width as f32 / 2.0 - PLAYER_SIZE.x / 2.0,
+            self.height as f32 - PLAYER_SIZE.y
+        );
+        let ball_pos = player_pos + vec2(
+            PLAYER_SIZE.x / 2.0 - BALL_RADIUS,
+            -BALL_RADIUS * 2.0
+        );
+
+        self.player.size = PLAYER_SIZE;
+        self.player.position = player_pos;
+
+        self.balls.clear();
+        let mut ball = Ball::new(ball_pos, BALL_RADIUS, self.initial_ball_velocity());
+        ball.game_object.id = self.new_object_id();
+        self.balls.push(ball);
+
+        self.player.color = vec3(1.0, 1.0, 1.0);
+        self.effects = Effects::default();
+        self.lasers.clear();
+        self.shield = false;
+        self.shake_time = 0.0;
+
+        self.power_ups.clear();
+    }
+
+    // a new id for an object coming into play, so that it isn't mistaken for one that was
+    // removed from its list during the same step
+    pub fn new_object_id(&mut self) -> u32 {
+        let id = self.next_object_id;
+        self.next_object_id += 1;
+        id
+    }
+
+    // launch velocity of the ball, with the speed the current level asks for
+    fn initial_ball_velocity(&self) -> Vector2<f32> {
+        match self.levels.get(self.actual_level).and_then(|level| level.info.ball_speed) {
+            Some(speed) => INITIAL_BALL_VELOCITY.normalize() * speed,
+            None => INITIAL_BALL_VELOCITY
+        }
+    }
+
+    // awards extra lives for every score threshold passed since the last call
+    fn check_extra_lives(&mut self) {
+        while self.extra_life_score > 0 && self.score.points >= self.next_extra_life {
+            self.award_life();
+            self.next_extra_life += self.extra_life_score;
+        }
+    }
+
+    // moves a ball over a whole update, resolving every collision on the way in the order
+    // they happen so a fast ball can't tunnel through thin bricks or the paddle,
+    // returns whether it destroyed any brick
+    fn move_ball(&mut self, ball: usize, dt: f32) -> bool {
+        let mut bricks_destroyed = false;
+        let mut remaining = dt;
+        for _ in 0..MAX_BALL_COLLISIONS {
+            if self.balls[ball].stuck {
+                break;
+            }
+            let motion = self.balls[ball].game_object.velocity * remaining;
+            let (hit, obstacle) = match self.first_ball_hit(ball, motion) {
+                Some(first) => first,
+                None => {
+                    self.balls[ball].game_object.position += motion;
+                    break;
+                }
+            };
+            // move up to the contact
+            self.balls[ball].game_object.position += motion * hit.time;
+            remaining *= 1.0 - hit.time;
+
+            let bounce = match obstacle {
+                Obstacle::Wall => true,
+                Obstacle::Brick(index) => {
+                    let (bounce, destroyed) = self.hit_brick(ball, index);
+                    bricks_destroyed |= destroyed;
+                    bounce
+                },
+                Obstacle::Paddle => {
+                    self.bounce_off_paddle(ball);
+                    false
+                },
+                Obstacle::Shield => {
+                    // the shield only holds once
+                    self.shield = false;
+                    true
+                },
+            };
+            if bounce {
+                let velocity = self.balls[ball].game_object.velocity;
+                self.balls[ball].game_object.velocity = reflect(velocity, hit.normal);
+            }
+        }
+        bricks_destroyed
+    }
+
+    // earliest obstacle the ball runs into over `motion`
+    fn first_ball_hit(&self, ball: usize, motion: Vector2<f32>) -> Option<(Hit, Obstacle)> {
+        let radius = self.balls[ball].radius;
+        let center = self.balls[ball].game_object.position + vec2(radius, radius);
+        let mut first: Option<(Hit, Obstacle)> = None;
+        let mut consider = |hit: Option<Hit>, obstacle: Obstacle| {
+            if let Some(hit) = hit {
+                if first.map_or(true, |(first, _)| hit.time < first.time) {
+                    first = Some((hit, obstacle));
+                }
+            }
+        };
+
+        // left, right and top walls, the bottom is open
+        if motion.x < 0.0 {
+            consider(wall_hit(center.x - radius, motion.x, vec2(1.0, 0.0)), Obstacle::Wall);
+        } else if motion.x > 0.0 {
+            consider(wall_hit(self.width as f32 - center.x - radius, -motion.x, vec2(-1.0, 0.0)), Obstacle::Wall);
+        }
+        if motion.y < 0.0 {
+            consider(wall_hit(center.y - radius, motion.y, vec2(0.0, 1.0)), Obstacle::Wall);
+        } else if motion.y > 0.0 && self.shield {
+            consider(wall_hit(self.height as f32 - center.y - radius, -motion.y, vec2(0.0, -1.0)), Obstacle::Shield);
+        }
+        // only the bricks on the tiles the ball sweeps over
+        let level = &self.levels[self.actual_level];
+        let start = center.zip(center + motion, f32::min) - vec2(radius, radius);
+        let end = center.zip(center + motion, f32::max) + vec2(radius, radius);
+        for i in level.bricks_in(start, end) {
+            consider(sweep_circle_collision(center, radius, motion, &level.bricks[i].game_object), Obstacle::Brick(i));
+        }
+        consider(sweep_circle_collision(center, radius, motion, &self.player), Obstacle::Paddle);
+
+        first
+    }
+
+    // damages a brick the ball ran into, returns whether the ball bounces off
+    // and whether the brick was destroyed
+    fn hit_brick(&mut self, ball: usize, index: usize) -> (bool, bool) {
+        let passthrough = self.balls[ball].passthrough;
+        let level = &mut self.levels[self.actual_level];
+        let brick = &level.bricks[index];
+        // damage block if not solid (some blocks only give way to a passthrough ball)
+        if !brick.game_object.is_solid && (passthrough || !brick.passthrough_only) {
+            // a passthrough ball smashes through whatever hit points are left
+            let damage = if passthrough { brick.hit_points } else { 1 };
+            (!passthrough, self.damage_brick(index, damage))
+        } else { // if block is solid (or out of reach), enable shake effect
+            self.shake_time = 0.05;
+            self.effects.shake = true;
+            (true, false)
+        }
+    }
+
+    // tints the paddle and balls by the effects on them, all at once so that
+    // overlapping effects don't reset each other's tint
+    fn update_tints(&mut self) {
+        let sticky = self.balls.iter().any(|ball| ball.sticky);
+        self.player.color = if sticky { vec3(1.0, 0.5, 1.0) } else { vec3(1.0, 1.0, 1.0) };
+        for ball in self.balls.iter_mut() {
+            ball.game_object.color = if ball.passthrough { vec3(1.0, 0.5, 0.5) } else { vec3(1.0, 1.0, 1.0) };
+        }
+    }
+
+    // takes hit points off a brick and scores them, a destroyed brick may drop a power-up,
+    // returns whether it was destroyed
+    fn damage_brick(&mut self, index: usize, damage: u32) -> bool {
+        let level = &mut self.levels[self.actual_level];
+        let points = level.damage_brick(index, damage);
+        self.score.brick_hit(points);
+        let brick = &level.bricks[index];
+        if brick.game_object.destroyed {
+            let drops = match find_brick_type(&level.palette, brick.code) {
+                Some(brick_type) => brick_type.drops.as_slice(),
+                None => &[]
+            };
+            if let Some(mut power_up) = spawn_power_ups(brick.game_object.position, drops, &mut self.rng) {
+                power_up.game_object.id = self.next_object_id;
+                self.next_object_id += 1;
+                self.power_ups.push(power_up);
+            }
+        }
+        brick.game_object.destroyed
+    }
+
+    // shoots a laser bolt up from each end of the paddle
+    fn fire_lasers(&mut self) {
+        let y = self.player.position.y - LASER_SIZE.y;
+        for x in [self.player.position.x + 8.0, self.player.position.x + self.player.size.x - 8.0 - LASER_SIZE.x].iter() {
+            let mut laser = GameObject::new(vec2(*x, y), LASER_SIZE, vec2(0.0, -LASER_VELOCITY), vec3(1.0, 0.3, 0.3));
+            laser.id = self.new_object_id();
+            self.lasers.push(laser);
+        }
+        self.laser_cooldown = LASER_INTERVAL;
+    }
+
+    // moves the laser bolts up, each one damages the first brick in its way and is gone,
+    // returns whether any brick was destroyed
+    fn update_lasers(&mut self, dt: f32) -> bool {
+        self.laser_cooldown = (self.laser_cooldown - dt).max(0.0);
+        let mut bricks_destroyed = false;
+        for i in 0..self.lasers.len() {
+            let start = self.lasers[i].position;
+            let motion = self.lasers[i].velocity * dt;
+            self.lasers[i].position += motion;
+            // everything the bolt swept over, the lowest brick in there is hit first
+            let mut swept = self.lasers[i];
+            swept.size.y = start.y + swept.size.y - swept.position.y;
+            let level = &self.levels[self.actual_level];
+            let target = level.bricks_in(swept.position, swept.position + swept.size)
+                .filter(|brick| check_square_collision(&swept, &level.bricks[*brick].game_object))
+                .max_by(|a, b| level.bricks[*a].game_object.position.y.partial_cmp(&level.bricks[*b].game_object.position.y).unwrap());
+
+            if let Some(index) = target {
+                self.lasers[i].destroyed = true;
+                let brick = &level.bricks[index];
+                if !brick.game_object.is_solid && !brick.passthrough_only {
+                    bricks_destroyed |= self.damage_brick(index, 1);
+                }
+            } else if self.lasers[i].position.y + LASER_SIZE.y < 0.0 {
+                self.lasers[i].destroyed = true;
+            }
+        }
+        self.lasers.retain(|laser| !laser.destroyed);
+        bricks_destroyed
+    }
+
+    // steers every falling ball sideways towards the middle of the paddle, keeping its speed
+    fn pull_balls(&mut self, dt: f32) {
+        let center_board = self.player.position.x + self.player.size.x / 2.0;
+        for ball in self.balls.iter_mut().filter(|ball| !ball.stuck && ball.game_object.velocity.y > 0.0) {
+            let speed = length(ball.game_object.velocity);
+            let distance = center_board - (ball.game_object.position.x + ball.radius);
+            let x = clamp(ball.game_object.velocity.x + distance * MAGNET_PULL * dt, -0.8 * speed, 0.8 * speed);
+            ball.game_object.velocity = vec2(x, (speed * speed - x * x).sqrt());
+        }
+    }
+
+    // sends a ball back up at an angle depending on where it hit the paddle
+    fn bounce_off_paddle(&mut self, ball: usize) {
+        let ball = &mut self.balls[ball];
+        // check where it hit the board, and change velocity based on where it hit the board
+        let center_board: f32 = self.player.position.x + self.player.size.x / 2.0;
+        let distance: f32 = (ball.game_object.position.x + ball.radius) - center_board;
+        let percentage: f32 = distance / (self.player.size.x / 2.0);
+        // then move accordingly
+        let strength = 2.0;
+        let old_velocity = ball.game_object.velocity;
+        ball.game_object.velocity.x = INITIAL_BALL_VELOCITY.x * percentage * strength;
+        ball.game_object.velocity = ball.game_object.velocity.normalize() * length(old_velocity); // keep speed consistent over both axes (multiply by length of old velocity, so total strength is not changed)
+        // always bounce upwards, even off the sides of the paddle
+        ball.game_object.velocity.y = -ball.game_object.velocity.y.abs();
+
+        // if Sticky powerup is activated, also stick ball to paddle once new velocity vectors were calculated
+        ball.stuck = ball.sticky;
+        // touching the paddle ends the combo
+        self.score.break_combo();
+    }
+
+    pub fn do_collisions(&mut self) {
+        let mut picked_up = Vec::new();
+        // check collisions on PowerUps and if so, activate them
+        for power_up in &mut self.power_ups {
+            if !power_up.game_object.destroyed {
+                // first check if powerup passed bottom edge, if so: keep as inactive and destroy
+                if power_up.game_object.position.y >= self.height as f32 {
+                    power_up.game_object.destroyed = true;
+                }
+
+                if check_square_collision(&self.player, &power_up.game_object) {
+                    // collided with player, now activate powerup
+                    picked_up.push(power_up.kind);
+                    power_up.game_object.destroyed = true;
+                    self.score.add_bonus(POWER_UP_BONUS);
+                }
+            }
+        }
+        for kind in picked_up {
+            self.activate_power_up(kind);
+        }
+
+        self.check_extra_lives();
+    }
+
+    fn update_power_ups(&mut self, dt: f32) {
+        for power_up in &mut self.power_ups {
+            power_up.game_object.position += power_up.game_object.velocity * dt;
+        }
+        // drop power-ups that were picked up or missed
+        self.power_ups.retain(|pu| !pu.game_object.destroyed);
+    }
+
+    // applies the effect of a picked up power-up according to how it stacks
+    fn activate_power_up(&mut self, kind: &'static PowerUpKind) {
+        // instant effects are never undone
+        if kind.duration <= 0.0 {
+            (kind.activate)(self);
+            return;
+        }
+
+        let max_layers = match kind.stacking {
+            Stacking::Stack { max } => max.max(1),
+            Stacking::Refresh | Stacking::Exclusive { .. } => 1,
+        };
+        let layers = self.active_effects.iter().filter(|effect| effect.kind.name == kind.name).count() as u32;
+        if layers >= max_layers {
+            // nothing more is applied, the layer closest to running out starts over instead
+            let oldest = self.active_effects.iter_mut()
+                .filter(|effect| effect.kind.name == kind.name)
+                .min_by(|a, b| a.duration.partial_cmp(&b.duration).unwrap());
+            if let Some(effect) = oldest {
+                effect.duration = kind.duration;
+            }
+            return;
+        }
+
+        if let Stacking::Exclusive { group } = kind.stacking {
+            self.end_effects(|effect| effect.kind.stacking == Stacking::Exclusive { group: group });
+        }
+        (kind.activate)(self);
+        self.active_effects.push(ActiveEffect { kind: kind, duration: kind.duration });
+    }
+
+    fn update_effects(&mut self, dt: f32) {
+        for effect in self.active_effects.iter_mut() {
+            effect.duration -= dt;
+        }
+        self.end_effects(|effect| effect.duration <= 0.0);
+    }
+
+    // undoes the active effects `ends` picks, one deactivation per applied layer
+    fn end_effects<F: Fn(&ActiveEffect) -> bool>(&mut self, ends: F) {
+        let (ended, active): (Vec<ActiveEffect>, Vec<ActiveEffect>) = std::mem::take(&mut self.active_effects)
+            .into_iter()
+            .partition(|effect| ends(effect));
+        self.active_effects = active;
+        for effect in ended {
+            (effect.kind.deactivate)(self);
+        }
+    }
+}
+
+// when a ball `distance` away from a wall, closing in on it by `approach` (negative), touches it
+fn wall_hit(distance: f32, approach: f32, normal: Vector2<f32>) -> Option<Hit> {
+    let time = (-distance / approach).max(0.0);
+    if time <= 1.0 {
+        Some(Hit { time: time, normal: normal })
+    } else {
+        None
+    }
+}
+
+// tries the drops of a destroyed brick in order, spawning the first one that hits
+fn spawn_power_ups(pos: Vector2<f32>, drops: &[PowerUpDrop], rng: &mut ChaCha8Rng) -> Option<PowerUp> {
+    for drop in drops {
+        if power_up_should_spawn(drop.chance, rng) {
+            // drops of unknown power-ups never spawn anything
+            return find_power_up_kind(&drop.power_up).map(|kind| PowerUp::new(pos, kind));
+        }
+    }
+    None
+}
+
+fn power_up_should_spawn(chance: u32, rng: &mut ChaCha8Rng) -> bool {
+    if chance == 0 {
+        return false;
+    }
+    let random: u32 = rng.gen::<u32>() % chance;
+    random == 0
+}
+
+// stream of the game seed that particles draw from
+fn particle_rng(seed: u64) -> ChaCha8Rng {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(1);
+    rng
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::power_up::MAX_BALLS;
+
+    const DT: f32 = 1.0 / 120.0;
+
+    // a simulation on an 800x600 screen playing a single level laid out from `tiles`
+    fn simulation_with(tiles: Vec<Vec<u32>>) -> Simulation {
+        let mut level = GameLevel::new();
+        level.level_width = 800;
+        level.level_height = 300;
+        level.set_tiles(tiles);
+        let mut sim = Simulation::new(800, 600);
+        sim.levels.push(level);
+        sim
+    }
+
+    // sends a ball straight past the paddle
+    fn drain_ball(sim: &mut Simulation, ball: usize) {
+        let ball = &mut sim.balls[ball];
+        ball.stuck = false;
+        ball.game_object.position = vec2(0.0, sim.height as f32 - 1.0);
+        ball.game_object.velocity = vec2(0.0, 350.0);
+    }
+
+    // sets a ball loose with its center at `center`
+    fn launch_ball(sim: &mut Simulation, ball: usize, center: Vector2<f32>, velocity: Vector2<f32>) {
+        let ball = &mut sim.balls[ball];
+        ball.stuck = false;
+        ball.game_object.position = center - vec2(ball.radius, ball.radius);
+        ball.game_object.velocity = velocity;
+    }
+
+    fn ball_center(sim: &Simulation, ball: usize) -> Vector2<f32> {
+        let ball = &sim.balls[ball];
+        ball.game_object.position + vec2(ball.radius, ball.radius)
+    }
+
+    // 30 rows of tiles 10 pixels high spanning the whole width, with a brick on the given rows
+    fn thin_rows(bricks: &[usize]) -> Vec<Vec<u32>> {
+        (0..30).map(|row| vec![if bricks.contains(&row) { 2 } else { 0 }]).collect()
+    }
+
+    #[test]
+    fn ball_clears_a_single_brick_level() {
+        let mut sim = simulation_with(vec![vec![2]]);
+        assert!(!sim.is_level_completed());
+
+        sim.process_input(&PlayerInput { steer: 0.0, launch: true }, DT);
+        for _ in 0..240 {
+            sim.update(DT);
+            if sim.is_level_completed() {
+                break;
+            }
+        }
+        assert!(sim.is_level_completed());
+        assert_eq!(sim.lives, INITIAL_LIVES);
+        // the brick's points and the clear bonus
+        assert!(sim.score.points >= LEVEL_CLEAR_BONUS);
+    }
+
+    #[test]
+    fn losing_the_ball_costs_a_life() {
+        let mut sim = simulation_with(vec![vec![2, 2]]);
+        sim.levels[0].bricks[0].game_object.destroyed = true;
+        drain_ball(&mut sim, 0);
+        sim.update(DT);
+
+        assert_eq!(sim.lives, INITIAL_LIVES - 1);
+        assert!(!sim.is_game_over());
+        // a fresh ball waits on the paddle, the level is left as it was
+        assert_eq!(sim.balls.len(), 1);
+        assert!(sim.balls[0].stuck);
+        assert!(sim.levels[0].bricks[0].game_object.destroyed);
+    }
+
+    #[test]
+    fn game_over_restores_the_level_and_a_new_game_starts_over() {
+        let mut sim = simulation_with(vec![vec![2, 2]]);
+        sim.initial_lives = 1;
+        sim.new_game();
+        sim.levels[0].bricks[0].game_object.destroyed = true;
+        sim.score.brick_hit(10);
+
+        drain_ball(&mut sim, 0);
+        sim.update(DT);
+        assert!(sim.is_game_over());
+        assert!(sim.levels[0].bricks.iter().all(|brick| !brick.game_object.destroyed));
+        assert!(sim.balls[0].stuck);
+
+        sim.new_game();
+        assert_eq!(sim.lives, 1);
+        assert_eq!(sim.score.points, 0);
+    }
+
+    fn kind(name: &str) -> &'static PowerUpKind {
+        find_power_up_kind(name).unwrap()
+    }
+
+    fn layers(sim: &Simulation, name: &str) -> Vec<f32> {
+        sim.active_effects.iter().filter(|effect| effect.kind.name == name).map(|effect| effect.duration).collect()
+    }
+
+    #[test]
+    fn speed_stacks_up_to_three_layers_and_then_refreshes_the_shortest() {
+        let mut sim = simulation_with(vec![vec![2]]);
+        let speed = kind("speed");
+        let base_speed = sim.balls[0].game_object.velocity.magnitude();
+        for _ in 0..3 {
+            sim.activate_power_up(speed);
+            sim.update_effects(2.0);
+        }
+        assert_eq!(layers(&sim, "speed"), vec![9.0, 11.0, 13.0]);
+
+        sim.activate_power_up(speed);
+        assert_eq!(layers(&sim, "speed"), vec![15.0, 11.0, 13.0]);
+        let expected_speed = base_speed * 1.2 * 1.2 * 1.2;
+        assert!((sim.balls[0].game_object.velocity.magnitude() - expected_speed).abs() < 1e-3);
+    }
+
+    #[test]
+    fn increase_returns_the_paddle_to_its_size_once_every_layer_expired() {
+        let mut sim = simulation_with(vec![vec![2]]);
+        for _ in 0..3 {
+            sim.activate_power_up(kind("increase"));
+        }
+        assert_eq!(sim.player.size.x, PLAYER_SIZE.x + 150.0);
+
+        sim.update_effects(kind("increase").duration);
+        assert!(sim.active_effects.is_empty());
+        assert_eq!(sim.player.size, PLAYER_SIZE);
+    }
+
+    #[test]
+    fn chaos_ends_confuse() {
+        let mut sim = simulation_with(vec![vec![2]]);
+        sim.activate_power_up(kind("confuse"));
+        assert!(sim.effects.confuse);
+
+        sim.activate_power_up(kind("chaos"));
+        assert!(!sim.effects.confuse);
+        assert!(sim.effects.chaos);
+        assert!(layers(&sim, "confuse").is_empty());
+        assert_eq!(layers(&sim, "chaos").len(), 1);
+    }
+
+    #[test]
+    fn losing_a_life_mid_effect_restores_ball_speed_and_paddle_size() {
+        let mut sim = simulation_with(vec![vec![2]]);
+        let speed = sim.balls[0].game_object.velocity.magnitude();
+        let width = sim.player.size.x;
+        for _ in 0..4 {
+            sim.activate_power_up(kind("speed"));
+            sim.activate_power_up(kind("increase"));
+        }
+        // both capped at three layers
+        assert_eq!(sim.active_effects.len(), 6);
+        assert!((sim.balls[0].game_object.velocity.magnitude() - speed * 1.2 * 1.2 * 1.2).abs() < 1e-3);
+        assert_eq!(sim.player.size.x, width + 150.0);
+
+        drain_ball(&mut sim, 0);
+        sim.update(DT);
+        assert_eq!(sim.lives, INITIAL_LIVES - 1);
+        assert!(sim.active_effects.is_empty());
+        assert!((sim.balls[0].game_object.velocity.magnitude() - speed).abs() < 1e-3);
+        assert_eq!(sim.player.size.x, width);
+    }
+
+    // plays a full grid with the given seed and the same scripted inputs, listing every
+    // power-up that dropped with where it dropped
+    fn drops_with_seed(seed: u64) -> Vec<(u32, &'static str, Vector2<f32>)> {
+        let mut sim = simulation_with(vec![vec![2; 15]; 8]);
+        sim.seed = seed;
+        sim.new_game();
+        let mut drops: Vec<(u32, &'static str, Vector2<f32>)> = Vec::new();
+        for step in 0..120 * 30 {
+            let input = PlayerInput { steer: ((step / 90) % 3) as f32 - 1.0, launch: true };
+            sim.process_input(&input, DT);
+            sim.update(DT);
+            for power_up in sim.power_ups.iter() {
+                if !drops.iter().any(|(id, _, _)| *id == power_up.game_object.id) {
+                    drops.push((power_up.game_object.id, power_up.kind.name, power_up.game_object.position));
+                }
+            }
+        }
+        drops
+    }
+
+    #[test]
+    fn same_seed_and_inputs_drop_the_same_power_ups() {
+        let drops = drops_with_seed(7);
+        assert!(!drops.is_empty());
+        assert_eq!(drops, drops_with_seed(7));
+        assert_ne!(drops, drops_with_seed(8));
+    }
+
+    #[test]
+    fn fast_ball_cannot_tunnel_through_a_thin_brick_or_the_paddle() {
+        // a step long enough to carry the ball further than the brick and the paddle are thick
+        let dt = 0.15;
+        let mut sim = simulation_with(thin_rows(&[20]));
+        launch_ball(&mut sim, 0, vec2(400.0, 240.0), vec2(0.0, -INITIAL_BALL_VELOCITY.magnitude()));
+        for _ in 0..3 {
+            sim.activate_power_up(kind("speed"));
+        }
+        assert!(sim.balls[0].game_object.velocity.magnitude() * dt > 10.0 + 2.0 * BALL_RADIUS);
+        sim.update(dt);
+        assert!(sim.levels[0].bricks[0].game_object.destroyed);
+        assert!(sim.balls[0].game_object.velocity.y > 0.0);
+        assert!(ball_center(&sim, 0).y > 210.0 + BALL_RADIUS);
+
+        let speed = sim.balls[0].game_object.velocity.magnitude();
+        let paddle = sim.player.position;
+        launch_ball(&mut sim, 0, paddle + vec2(50.0, -40.0), vec2(0.0, speed));
+        sim.update(dt);
+        assert_eq!(sim.lives, INITIAL_LIVES);
+        assert!(sim.balls[0].game_object.velocity.y < 0.0);
+        assert!(ball_center(&sim, 0).y < sim.player.position.y - BALL_RADIUS);
+    }
+
+    #[test]
+    fn corner_hit_reflects_along_the_corner_normal() {
+        // the brick of the middle tile
+        let mut sim = simulation_with(vec![vec![0, 0, 0], vec![0, 2, 0], vec![0, 0, 0]]);
+        let unit = sim.levels[0].unit_size();
+        let corner = vec2(unit.x, unit.y * 2.0);
+        // heading straight for the corner, so the circle touches it before either side
+        launch_ball(&mut sim, 0, corner + vec2(-50.0, 50.0), vec2(300.0, -300.0));
+        for _ in 0..60 {
+            sim.update(DT);
+            if sim.levels[0].bricks[0].game_object.destroyed {
+                break;
+            }
+        }
+        assert!(sim.levels[0].bricks[0].game_object.destroyed);
+        let velocity = sim.balls[0].game_object.velocity;
+        assert!((velocity - vec2(-300.0, 300.0)).magnitude() < 1e-2, "{:?}", velocity);
+    }
+
+    #[test]
+    fn hits_within_a_step_are_resolved_in_the_order_they_happen() {
+        // heading up and right, the ball reaches the right wall first and the brick after
+        let mut sim = simulation_with(thin_rows(&[20]));
+        launch_ball(&mut sim, 0, vec2(780.0, 240.0), vec2(1000.0, -1000.0));
+        sim.update(0.1);
+
+        assert!(sim.levels[0].bricks[0].game_object.destroyed);
+        let velocity = sim.balls[0].game_object.velocity;
+        assert!(velocity.x < 0.0 && velocity.y > 0.0);
+        // wall at (787.5, 232.5), brick at (777.5, 222.5), then down and left for the rest of the step
+        assert!((ball_center(&sim, 0) - vec2(695.0, 305.0)).magnitude() < 1e-2, "{:?}", ball_center(&sim, 0));
+    }
+
+    #[test]
+    fn multiball_splits_a_ball_into_three() {
+        let mut sim = simulation_with(vec![vec![2]]);
+        launch_ball(&mut sim, 0, vec2(400.0, 450.0), INITIAL_BALL_VELOCITY);
+        sim.activate_power_up(kind("multiball"));
+
+        assert_eq!(sim.balls.len(), 3);
+        let speed = INITIAL_BALL_VELOCITY.magnitude();
+        for (i, ball) in sim.balls.iter().enumerate() {
+            assert!(!ball.stuck);
+            assert!((ball.game_object.velocity.magnitude() - speed).abs() < 1e-3);
+            assert!(sim.balls[..i].iter().all(|other| other.game_object.velocity != ball.game_object.velocity));
+            assert!(sim.balls[..i].iter().all(|other| other.game_object.id != ball.game_object.id));
+        }
+    }
+
+    #[test]
+    fn multiball_stops_splitting_at_the_most_balls() {
+        let mut sim = simulation_with(vec![vec![2]]);
+        launch_ball(&mut sim, 0, vec2(400.0, 450.0), INITIAL_BALL_VELOCITY);
+        sim.activate_power_up(kind("multiball"));
+        sim.activate_power_up(kind("multiball"));
+        assert_eq!(sim.balls.len(), 9);
+        sim.activate_power_up(kind("multiball"));
+        assert_eq!(sim.balls.len(), MAX_BALLS);
+    }
+
+    #[test]
+    fn only_the_last_ball_to_drain_costs_a_life() {
+        let mut sim = simulation_with(vec![vec![2]]);
+        launch_ball(&mut sim, 0, vec2(400.0, 450.0), INITIAL_BALL_VELOCITY);
+        sim.activate_power_up(kind("multiball"));
+        drain_ball(&mut sim, 0);
+        drain_ball(&mut sim, 2);
+        sim.update(DT);
+        assert_eq!(sim.lives, INITIAL_LIVES);
+        assert_eq!(sim.balls.len(), 1);
+        assert!(!sim.balls[0].stuck);
+
+        drain_ball(&mut sim, 0);
+        sim.update(DT);
+        assert_eq!(sim.lives, INITIAL_LIVES - 1);
+        assert_eq!(sim.balls.len(), 1);
+        assert!(sim.balls[0].stuck);
+    }
+
+    #[test]
+    fn laser_bolts_damage_the_first_brick_above_them() {
+        let tiles: Vec<Vec<u32>> = (0..30).map(|row| vec![if row == 5 || row == 15 { 2 } else { 0 }; 15]).collect();
+        let mut sim = simulation_with(tiles);
+        sim.activate_power_up(kind("laser"));
+        sim.fire_lasers();
+        let unit = sim.levels[0].unit_size();
+        // the bricks of the lower row come after the 15 of the upper one
+        let mut targets: Vec<usize> = sim.lasers.iter().map(|laser| 15 + (laser.position.x / unit.x) as usize).collect();
+        targets.sort();
+        for _ in 0..120 {
+            sim.update(DT);
+        }
+
+        assert!(sim.lasers.is_empty());
+        let destroyed: Vec<usize> = sim.levels[0].bricks.iter().enumerate()
+            .filter(|(_, brick)| brick.game_object.destroyed)
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(destroyed, targets);
+    }
+
+    #[test]
+    fn shield_saves_a_single_ball() {
+        let mut sim = simulation_with(vec![vec![2]]);
+        sim.activate_power_up(kind("shield"));
+        for saved in [true, false] {
+            launch_ball(&mut sim, 0, vec2(50.0, 560.0), vec2(0.0, 350.0));
+            for _ in 0..60 {
+                sim.update(DT);
+            }
+            assert_eq!(sim.lives, if saved { INITIAL_LIVES } else { INITIAL_LIVES - 1 });
+            assert!(!sim.shield);
+        }
+    }
+
+    #[test]
+    fn magnet_pulls_a_falling_ball_onto_the_paddle_and_lets_it_go_back_up() {
+        // thrown down well to the side of the paddle, only the magnet gets it there
+        let throw = |magnet: bool| {
+            let mut sim = simulation_with(vec![vec![2]]);
+            if magnet {
+                sim.activate_power_up(kind("magnet"));
+            }
+            launch_ball(&mut sim, 0, vec2(150.0, 350.0), vec2(0.0, INITIAL_BALL_VELOCITY.magnitude()));
+            let mut steps = 0;
+            while sim.balls[0].game_object.velocity.y > 0.0 && sim.lives == INITIAL_LIVES && steps < 240 {
+                sim.update(DT);
+                steps += 1;
+            }
+            sim
+        };
+        assert_eq!(throw(false).lives, INITIAL_LIVES - 1);
+
+        let mut sim = throw(true);
+        assert_eq!(sim.lives, INITIAL_LIVES);
+        assert!(sim.balls[0].game_object.velocity.y < 0.0);
+        assert!((sim.balls[0].game_object.velocity.magnitude() - INITIAL_BALL_VELOCITY.magnitude()).abs() < 1e-2);
+        // a rising ball is left alone
+        let velocity = sim.balls[0].game_object.velocity;
+        sim.update(DT);
+        assert_eq!(sim.balls[0].game_object.velocity, velocity);
+    }
+
+    #[test]
+    fn slow_and_shrink_are_undone_once_they_expire() {
+        let mut sim = simulation_with(vec![vec![2]]);
+        let velocity = sim.balls[0].game_object.velocity;
+        let width = sim.player.size.x;
+        sim.activate_power_up(kind("slow"));
+        sim.activate_power_up(kind("shrink"));
+        assert!((sim.balls[0].game_object.velocity - velocity * 0.7).magnitude() < 1e-3);
+        assert_eq!(sim.player.size.x, width - 40.0);
+
+        sim.update_effects(kind("slow").duration.max(kind("shrink").duration));
+        assert!(sim.active_effects.is_empty());
+        assert!((sim.balls[0].game_object.velocity - velocity).magnitude() < 1e-3);
+        assert_eq!(sim.player.size.x, width);
+    }
+
+    #[test]
+    fn extra_life_is_capped_at_the_most_lives() {
+        let mut sim = simulation_with(vec![vec![2]]);
+        sim.max_lives = INITIAL_LIVES + 1;
+        sim.activate_power_up(kind("life"));
+        assert_eq!(sim.lives, INITIAL_LIVES + 1);
+        sim.activate_power_up(kind("life"));
+        assert_eq!(sim.lives, INITIAL_LIVES + 1);
+    }
+}
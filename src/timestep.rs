@@ -0,0 +1,40 @@
+// Hands out variable frame time as a whole number of fixed-size simulation steps
+pub struct FixedTimestep {
+    // duration of a single step in seconds
+    pub step: f32,
+    // maximum number of steps run per frame before the backlog is dropped
+    pub max_steps: u32,
+    accumulator: f32,
+}
+
+impl FixedTimestep {
+    pub fn new(rate: f32, max_steps: u32) -> Self {
+        FixedTimestep {
+            step: 1.0 / rate,
+            max_steps: max_steps,
+            accumulator: 0.0,
+        }
+    }
+
+    // adds the frame time and returns how many steps should be simulated now
+    pub fn advance(&mut self, frame_time: f32) -> u32 {
+        self.accumulator += frame_time;
+        let mut steps = (self.accumulator / self.step) as u32;
+
+        if steps > self.max_steps {
+            // too far behind (loading, window dragged, debugger...): drop the backlog
+            // instead of spiralling into ever longer frames
+            steps = self.max_steps;
+            self.accumulator %= self.step;
+        } else {
+            self.accumulator -= steps as f32 * self.step;
+        }
+
+        steps
+    }
+
+    // how far (0..1) the current frame is between the last two steps
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / self.step
+    }
+}